@@ -0,0 +1,8 @@
+-- Seeds the initial admin user (username: admin, password: everythinghastostartsomewhere).
+-- The password is meant to be changed right after the first deployment.
+INSERT INTO users (user_id, username, password_hash)
+VALUES (
+    'ddf8994f-d522-4659-8d02-c1d479057be6',
+    'admin',
+    '$argon2id$v=19$m=15000,t=2,p=1$xkZUnGHgQlDLYi0nVxCX6A$EwOc7Uv4xwqWJlTpt03rGh7Z4FdpCvtcv/WUxLwv5+Q'
+);
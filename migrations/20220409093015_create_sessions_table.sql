@@ -0,0 +1,6 @@
+CREATE TABLE sessions(
+    session_key TEXT NOT NULL,
+    PRIMARY KEY (session_key),
+    state TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,18 @@
+CREATE TABLE newsletter_issues(
+    newsletter_issue_id uuid NOT NULL,
+    PRIMARY KEY(newsletter_issue_id),
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    published_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE issue_delivery_queue(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    n_retries SMALLINT NOT NULL DEFAULT 0,
+    execute_after TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY(newsletter_issue_id, subscriber_id)
+);
@@ -0,0 +1,10 @@
+ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT NULL;
+
+-- Existing subscribers get a random 25 characters long alphanumeric token, the same shape as the
+-- tokens generated by the application.
+UPDATE subscriptions
+    SET unsubscribe_token = substr(replace(gen_random_uuid()::text, '-', ''), 1, 25)
+    WHERE unsubscribe_token IS NULL;
+
+ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
+ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_unsubscribe_token_key UNIQUE (unsubscribe_token);
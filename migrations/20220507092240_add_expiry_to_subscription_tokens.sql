@@ -0,0 +1,9 @@
+ALTER TABLE subscription_tokens
+    ADD COLUMN created_at timestamptz NOT NULL DEFAULT now(),
+    ADD COLUMN expires_at timestamptz NULL,
+    ADD COLUMN consumed_at timestamptz NULL;
+
+-- Tokens handed out before the expiry was introduced get the default lifetime of one day.
+UPDATE subscription_tokens SET expires_at = created_at + interval '1 day';
+
+ALTER TABLE subscription_tokens ALTER COLUMN expires_at SET NOT NULL;
@@ -0,0 +1,7 @@
+CREATE TABLE rate_limit_buckets(
+    key TEXT NOT NULL,
+    PRIMARY KEY (key),
+    tokens DOUBLE PRECISION NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+    full_at TIMESTAMPTZ NOT NULL
+);
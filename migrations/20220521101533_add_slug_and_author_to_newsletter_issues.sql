@@ -0,0 +1,14 @@
+ALTER TABLE newsletter_issues
+    ADD COLUMN slug TEXT NULL,
+    ADD COLUMN author_id uuid NULL REFERENCES users (user_id) ON DELETE SET NULL;
+
+-- The issues published so far get their id appended, there is no way to tell who wrote them.
+UPDATE newsletter_issues
+SET slug = COALESCE(
+        NULLIF(trim(BOTH '-' FROM lower(regexp_replace(title, '[^a-zA-Z0-9]+', '-', 'g'))), ''),
+        'issue'
+    ) || '-' || left(newsletter_issue_id::text, 8);
+
+ALTER TABLE newsletter_issues
+    ALTER COLUMN slug SET NOT NULL,
+    ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
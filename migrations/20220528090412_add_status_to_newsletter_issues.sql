@@ -0,0 +1,6 @@
+-- Scheduled issues keep their send time in `published_at` until the scheduler publishes them.
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT NOT NULL DEFAULT 'PUBLISHED';
+ALTER TABLE newsletter_issues ALTER COLUMN status DROP DEFAULT;
+
+CREATE INDEX newsletter_issues_scheduled_idx ON newsletter_issues (published_at)
+    WHERE status = 'SCHEDULED';
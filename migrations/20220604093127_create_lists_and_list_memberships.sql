@@ -0,0 +1,43 @@
+CREATE TABLE lists(
+    list_id uuid NOT NULL,
+    PRIMARY KEY (list_id),
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Subscriptions which do not name any list end up on the default one.
+CREATE UNIQUE INDEX lists_single_default_idx ON lists (is_default) WHERE is_default;
+
+CREATE TABLE list_memberships(
+    list_id uuid NOT NULL
+        REFERENCES lists (list_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (list_id, subscriber_id)
+);
+
+CREATE TABLE newsletter_issue_lists(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    list_id uuid NOT NULL
+        REFERENCES lists (list_id) ON DELETE CASCADE,
+    PRIMARY KEY (newsletter_issue_id, list_id)
+);
+
+-- Everything published so far went to the single implicit list, which becomes the default one.
+INSERT INTO lists (list_id, slug, name, is_default)
+VALUES (gen_random_uuid(), 'newsletter', 'Newsletter', true);
+
+INSERT INTO list_memberships (list_id, subscriber_id, status, created_at)
+SELECT l.list_id, s.id, s.status, s.subscribed_at
+FROM subscriptions s, lists l
+WHERE l.is_default;
+
+INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+SELECT i.newsletter_issue_id, l.list_id
+FROM newsletter_issues i, lists l
+WHERE l.is_default;
@@ -0,0 +1,5 @@
+ALTER TABLE subscriptions ADD COLUMN frequency TEXT NOT NULL DEFAULT 'EVERY_ISSUE';
+ALTER TABLE subscriptions ADD COLUMN last_delivered_at TIMESTAMPTZ NULL;
+
+-- Set on the tokens which confirm a change of address rather than a subscription.
+ALTER TABLE subscription_tokens ADD COLUMN new_email TEXT NULL;
@@ -0,0 +1,24 @@
+-- Issues sent to a list with tracking disabled carry no tracking pixel.
+ALTER TABLE lists ADD COLUMN tracking_enabled BOOLEAN NOT NULL DEFAULT true;
+
+CREATE TABLE newsletter_deliveries(
+    delivery_id uuid NOT NULL,
+    PRIMARY KEY (delivery_id),
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    delivered_at TIMESTAMPTZ NOT NULL,
+    tracked BOOLEAN NOT NULL
+);
+
+CREATE INDEX newsletter_deliveries_subscriber_id_idx ON newsletter_deliveries (subscriber_id);
+
+CREATE TABLE email_opens(
+    delivery_id uuid NOT NULL
+        REFERENCES newsletter_deliveries (delivery_id) ON DELETE CASCADE,
+    opened_at TIMESTAMPTZ NOT NULL,
+    user_agent TEXT
+);
+
+CREATE INDEX email_opens_delivery_id_idx ON email_opens (delivery_id);
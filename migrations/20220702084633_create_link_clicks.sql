@@ -0,0 +1,9 @@
+CREATE TABLE link_clicks(
+    delivery_id uuid NOT NULL
+        REFERENCES newsletter_deliveries (delivery_id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    clicked_at TIMESTAMPTZ NOT NULL,
+    user_agent TEXT
+);
+
+CREATE INDEX link_clicks_delivery_id_idx ON link_clicks (delivery_id);
@@ -0,0 +1,14 @@
+-- Delivery problems reported by the email provider, one row per event so that retried webhook
+-- calls are only processed once.
+CREATE TABLE email_events(
+    event_id TEXT NOT NULL,
+    PRIMARY KEY (event_id),
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    event_type TEXT NOT NULL,
+    reason TEXT,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX email_events_subscriber_id_idx ON email_events (subscriber_id);
@@ -0,0 +1,9 @@
+-- Confirmation emails sent on behalf of an import, one per subscription token.
+CREATE TABLE confirmation_email_queue(
+    subscription_token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    n_retries SMALLINT NOT NULL DEFAULT 0,
+    execute_after TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY(subscription_token)
+);
@@ -0,0 +1,109 @@
+use crate::authentication::{validate_credentials, Credentials};
+use crate::routes::ApiError;
+use actix_web::dev::Payload;
+use actix_web::http::header::HeaderMap;
+use actix_web::{web, FromRequest, HttpRequest};
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let credentials = basic_authentication(req.headers());
+        let db_pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+        Box::pin(async move {
+            let credentials = credentials.map_err(ApiError::AuthenticationError)?;
+            let db_pool = db_pool.context("Database connection pool is not registered.")?;
+            let user_id = validate_credentials(credentials, &db_pool).await?;
+
+            Ok(AuthenticatedUser { user_id })
+        })
+    }
+}
+
+pub fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing.")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string.")?;
+    let base64_encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'.")?;
+    let decoded_bytes = base64::decode_config(base64_encoded_segment, base64::STANDARD)
+        .context("Failed to base64-decode 'Basic' credentials.")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8.")?;
+
+    let mut credentials = decoded_credentials.splitn(2, ':');
+    let username = credentials
+        .next()
+        .context("A username must be provided in 'Basic' auth.")?
+        .to_string();
+    let password = credentials
+        .next()
+        .context("A password must be provided in 'Basic' auth.")?
+        .to_string();
+
+    Ok(Credentials {
+        username,
+        password: Secret::new(password),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::basic_authentication;
+    use actix_web::http::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+    use claim::{assert_err, assert_ok};
+    use secrecy::ExposeSecret;
+
+    fn headers_with_authorization(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn valid_basic_credentials_are_parsed() {
+        let encoded = base64::encode("ursula:le:guin");
+        let headers = headers_with_authorization(&format!("Basic {}", encoded));
+
+        let credentials = basic_authentication(&headers);
+
+        assert_ok!(&credentials);
+        let credentials = credentials.unwrap();
+        assert_eq!(credentials.username, "ursula");
+        assert_eq!(credentials.password.expose_secret(), "le:guin");
+    }
+
+    #[test]
+    fn missing_authorization_header_is_rejected() {
+        assert_err!(basic_authentication(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn non_basic_authorization_scheme_is_rejected() {
+        let headers = headers_with_authorization("Bearer some-token");
+        assert_err!(basic_authentication(&headers));
+    }
+
+    #[test]
+    fn credentials_without_a_password_are_rejected() {
+        let encoded = base64::encode("ursula");
+        let headers = headers_with_authorization(&format!("Basic {}", encoded));
+        assert_err!(basic_authentication(&headers));
+    }
+}
@@ -0,0 +1,9 @@
+mod basic_auth;
+mod password;
+mod session_auth;
+
+pub use basic_auth::{basic_authentication, AuthenticatedUser};
+pub use password::{
+    change_password, compute_password_hash, validate_credentials, AuthError, Credentials,
+};
+pub use session_auth::LoggedInUser;
@@ -0,0 +1,169 @@
+use crate::telemetry::spawn_blocking_with_tracing;
+use anyhow::Context;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use thiserror::Error;
+use uuid::Uuid;
+
+// Verified against when the username is unknown, so that both branches take the same time.
+const FALLBACK_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$yo/0hNH95bCRGo8J7H7SPg$MK0qDVUoL2Ak+q1GPgVZEUutRGG52OZi8U6d6NhkWAQ";
+
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid credentials.")]
+    InvalidCredentials(#[source] anyhow::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+#[derive(Debug)]
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(credentials, db_pool),
+    fields(
+        username = %credentials.username
+    )
+)]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    db_pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    let mut expected_password_hash = Secret::new(FALLBACK_PASSWORD_HASH.to_string());
+
+    if let Some((stored_user_id, stored_password_hash)) =
+        get_stored_credentials(&credentials.username, db_pool).await?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await
+    .context("Failed to spawn blocking task.")??;
+
+    user_id
+        .ok_or_else(|| anyhow::anyhow!("Unknown username."))
+        .map_err(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(db_pool))]
+async fn get_stored_credentials(
+    username: &str,
+    db_pool: &PgPool,
+) -> Result<Option<(Uuid, Secret<String>)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, password_hash
+        FROM users
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve stored credentials.")?
+    .map(|r| (r.user_id, Secret::new(r.password_hash)));
+
+    Ok(row)
+}
+
+#[tracing::instrument(
+    name = "Verify password hash",
+    skip(expected_password_hash, password_candidate)
+)]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .context("Invalid password.")
+        .map_err(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Change password", skip(password, db_pool))]
+pub async fn change_password(
+    user_id: Uuid,
+    password: Secret<String>,
+    db_pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task.")??;
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2
+        "#,
+        password_hash.expose_secret(),
+        user_id
+    )
+    .execute(db_pool)
+    .await
+    .context("Failed to change user's password in the database.")?;
+
+    Ok(())
+}
+
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).unwrap(),
+    )
+    .hash_password(password.expose_secret().as_bytes(), &salt)?
+    .to_string();
+
+    Ok(Secret::new(password_hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::authentication::password::{compute_password_hash, verify_password_hash};
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    #[test]
+    fn a_password_matches_its_own_hash() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        let password_hash = compute_password_hash(password.clone()).unwrap();
+
+        assert_ok!(verify_password_hash(password_hash, password));
+    }
+
+    #[test]
+    fn a_different_password_does_not_match_the_hash() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        let password_hash = compute_password_hash(password).unwrap();
+
+        let other_password = Secret::new("incorrect horse battery staple".to_string());
+        assert_err!(verify_password_hash(password_hash, other_password));
+    }
+
+    #[test]
+    fn a_malformed_hash_is_rejected() {
+        let password_hash = Secret::new("not-a-phc-string".to_string());
+        let password = Secret::new("correct horse battery staple".to_string());
+
+        assert_err!(verify_password_hash(password_hash, password));
+    }
+}
@@ -0,0 +1,39 @@
+use crate::session::TypedSession;
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::header::LOCATION;
+use actix_web::{FromRequest, HttpRequest, HttpResponse};
+use std::future::Future;
+use std::pin::Pin;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct LoggedInUser {
+    pub user_id: Uuid,
+}
+
+impl FromRequest for LoggedInUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let session = TypedSession::extract(req);
+
+        Box::pin(async move {
+            match session
+                .await?
+                .get_user_id()
+                .map_err(ErrorInternalServerError)?
+            {
+                Some(user_id) => Ok(LoggedInUser { user_id }),
+                None => {
+                    let response = HttpResponse::SeeOther()
+                        .insert_header((LOCATION, "/login"))
+                        .finish();
+                    let e = anyhow::anyhow!("The user has not logged in.");
+                    Err(InternalError::from_response(e, response).into())
+                }
+            }
+        })
+    }
+}
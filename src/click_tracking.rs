@@ -0,0 +1,76 @@
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+static HREF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)(\shref\s*=\s*)(?:"([^"]*)"|'([^']*)')"#)
+        .expect("A valid regular expression.")
+});
+
+pub fn rewrite_links(html: &str, rewrite: impl Fn(&str) -> String) -> String {
+    HREF.replace_all(html, |captures: &Captures| {
+        let (quote, target) = match (captures.get(2), captures.get(3)) {
+            (Some(target), _) => ('"', target.as_str()),
+            (_, Some(target)) => ('\'', target.as_str()),
+            _ => unreachable!("The expression captures either target."),
+        };
+        // The attribute holds HTML, the redirect has to point to the URL the browser would open.
+        let target = target.trim().replace("&amp;", "&");
+        if is_web_link(&target) {
+            format!("{}{}{}{}", &captures[1], quote, rewrite(&target), quote)
+        } else {
+            captures[0].to_string()
+        }
+    })
+    .into_owned()
+}
+
+pub fn is_web_link(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::click_tracking::{is_web_link, rewrite_links};
+
+    fn tracked(url: &str) -> String {
+        format!("https://tracker/{}", url.len())
+    }
+
+    #[test]
+    fn web_links_are_rewritten_whatever_their_quotes() {
+        let html = r#"<a href="https://example.com/a">a</a> <a class="b" HREF = 'http://example.com/bb'>b</a>"#;
+
+        assert_eq!(
+            rewrite_links(html, tracked),
+            r#"<a href="https://tracker/21">a</a> <a class="b" HREF = 'https://tracker/21'>b</a>"#
+        );
+    }
+
+    #[test]
+    fn other_links_and_attributes_are_left_alone() {
+        let html = r##"<a href="#top">top</a> <a href="mailto:a@example.com">mail</a> <a data-href="https://example.com">x</a>"##;
+
+        assert_eq!(rewrite_links(html, tracked), html);
+    }
+
+    #[test]
+    fn entities_are_decoded_before_rewriting() {
+        let rewritten = rewrite_links(
+            r#"<a href="https://example.com/?a=1&amp;b=2">x</a>"#,
+            |url| {
+                assert_eq!(url, "https://example.com/?a=1&b=2");
+                "https://tracker".into()
+            },
+        );
+
+        assert_eq!(rewritten, r#"<a href="https://tracker">x</a>"#);
+    }
+
+    #[test]
+    fn only_http_and_https_are_web_links() {
+        assert!(is_web_link("HTTPS://example.com"));
+        assert!(!is_web_link("javascript:alert(1)"));
+        assert!(!is_web_link("//example.com"));
+    }
+}
@@ -1,7 +1,10 @@
 use crate::domain::SubscriberEmail;
+use crate::email_client::RetryPolicy;
+use crate::rate_limit::TokenBucket;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use std::convert::{TryFrom, TryInto};
+use std::time::Duration;
 
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct Settings {
@@ -10,6 +13,12 @@ pub struct Settings {
     pub tracing: TracingSettings,
     pub email_client: EmailClientSettings,
     pub template_engine: TemplateEngineSettings,
+    pub session: SessionSettings,
+    pub subscriptions: SubscriptionSettings,
+    pub branding: BrandingSettings,
+    pub rate_limit: RateLimitSettings,
+    #[serde(skip_deserializing)]
+    pub environment: Environment,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -17,6 +26,26 @@ pub struct ApplicationSettings {
     pub host: String,
     pub port: u16,
     pub base_url: String,
+    pub hmac_secret: Secret<String>,
+}
+
+impl ApplicationSettings {
+    const MIN_HMAC_SECRET_BYTES: usize = 32;
+
+    /// Whoever knows the secret can forge links for any subscriber - only `local` may keep the
+    /// well-known default.
+    fn check_hmac_secret(&self, environment: Environment) -> Result<(), String> {
+        if environment == Environment::Local
+            || self.hmac_secret.expose_secret().len() >= Self::MIN_HMAC_SECRET_BYTES
+        {
+            return Ok(());
+        }
+        Err(format!(
+            "application.hmac_secret must be at least {} bytes long, set it through \
+            APP_APPLICATION__HMAC_SECRET.",
+            Self::MIN_HMAC_SECRET_BYTES
+        ))
+    }
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -40,10 +69,75 @@ pub struct TracingSettings {
 
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct EmailClientSettings {
+    pub provider: EmailProvider,
     pub base_url: String,
     pub sender_email: String,
     pub api_key: Secret<String>,
     pub timeout_millis: u64,
+    pub retry: RetrySettings,
+    #[serde(default)]
+    pub smtp: Option<SmtpSettings>,
+    #[serde(default)]
+    pub outbox: Option<OutboxSettings>,
+    #[serde(default)]
+    pub sendgrid_webhook: Option<SendGridWebhookSettings>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailProvider {
+    SendGrid,
+    Postmark,
+    Smtp,
+    Outbox,
+    #[serde(rename = "memory")]
+    InMemory,
+}
+
+impl EmailProvider {
+    fn check_environment(self, environment: Environment) -> Result<(), String> {
+        match self {
+            EmailProvider::InMemory if environment != Environment::Local => Err(
+                "email_client.provider `memory` is only available in the local environment.".into(),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SendGridWebhookSettings {
+    pub verification_key: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct OutboxSettings {
+    pub directory: String,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    None,
+    StartTls,
+    Wrapper,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RetrySettings {
+    pub max_attempts: u32,
+    pub base_delay_millis: u64,
+    pub max_delay_millis: u64,
+    pub jitter_millis: u64,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -51,6 +145,61 @@ pub struct TemplateEngineSettings {
     pub templates_dir: String,
 }
 
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SessionSettings {
+    pub store: SessionStoreKind,
+    pub cookie_name: String,
+    pub secure_cookie: bool,
+    pub ttl_minutes: i64,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SubscriptionSettings {
+    pub confirmation_token_ttl_minutes: i64,
+    pub preferences_link_ttl_minutes: i64,
+    pub data_request_link_ttl_minutes: i64,
+    /// Link-prefetching mail scanners confirm the subscriptions too when set.
+    #[serde(default)]
+    pub one_click_confirmation: bool,
+    #[serde(default)]
+    pub confirmation_redirect_url: Option<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct BrandingSettings {
+    pub name: String,
+    #[serde(default)]
+    pub logo_url: Option<String>,
+    pub accent_color: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStoreKind {
+    Postgres,
+    InMemory,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RateLimitSettings {
+    pub store: RateLimitStoreKind,
+    pub subscriptions_per_client_ip: TokenBucketSettings,
+    pub confirmation_emails_per_address: TokenBucketSettings,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitStoreKind {
+    Postgres,
+    InMemory,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct TokenBucketSettings {
+    pub capacity: u32,
+    pub period_seconds: u64,
+}
+
 impl DatabaseSettings {
     pub fn without_db(&self) -> PgConnectOptions {
         let ssl_mode = if self.require_ssl {
@@ -75,10 +224,46 @@ impl EmailClientSettings {
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry.max_attempts.max(1),
+            base_delay: Duration::from_millis(self.retry.base_delay_millis),
+            max_delay: Duration::from_millis(self.retry.max_delay_millis),
+            jitter: Duration::from_millis(self.retry.jitter_millis),
+        }
+    }
+}
+
+impl SubscriptionSettings {
+    pub fn confirmation_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.confirmation_token_ttl_minutes)
+    }
+
+    pub fn preferences_link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.preferences_link_ttl_minutes)
+    }
+
+    pub fn data_request_link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.data_request_link_ttl_minutes)
+    }
 }
 
+impl TokenBucketSettings {
+    pub fn bucket(&self) -> TokenBucket {
+        TokenBucket {
+            capacity: self.capacity.max(1),
+            period: Duration::from_secs(self.period_seconds.max(1)),
+        }
+    }
+}
+
+/// Defaults to `Production` so that anything meant for development only stays disabled unless
+/// the local environment was explicitly selected.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum Environment {
     Local,
+    #[default]
     Production,
 }
 
@@ -118,5 +303,56 @@ pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     settings
         .merge(config::File::from(configuration_path.join(environment.as_str())).required(true))?;
     settings.merge(config::Environment::with_prefix("app").separator("__"))?;
-    settings.try_into()
+    let mut settings: Settings = settings.try_into()?;
+    settings
+        .application
+        .check_hmac_secret(environment)
+        .map_err(config::ConfigError::Message)?;
+    settings
+        .email_client
+        .provider
+        .check_environment(environment)
+        .map_err(config::ConfigError::Message)?;
+    settings.environment = environment;
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::{ApplicationSettings, EmailProvider, Environment};
+    use claim::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn application(hmac_secret: &str) -> ApplicationSettings {
+        ApplicationSettings {
+            host: "127.0.0.1".into(),
+            port: 8000,
+            base_url: "http://127.0.0.1:8000".into(),
+            hmac_secret: Secret::new(hmac_secret.into()),
+        }
+    }
+
+    #[test]
+    fn short_hmac_secret_is_rejected_outside_the_local_environment() {
+        let short = application("local-only");
+        let empty = application("");
+
+        assert_err!(short.check_hmac_secret(Environment::Production));
+        assert_err!(empty.check_hmac_secret(Environment::Production));
+        assert_ok!(short.check_hmac_secret(Environment::Local));
+    }
+
+    #[test]
+    fn long_hmac_secret_is_accepted_everywhere() {
+        let settings = application(&"x".repeat(32));
+
+        assert_ok!(settings.check_hmac_secret(Environment::Production));
+    }
+
+    #[test]
+    fn memory_provider_is_rejected_outside_the_local_environment() {
+        assert_err!(EmailProvider::InMemory.check_environment(Environment::Production));
+        assert_ok!(EmailProvider::InMemory.check_environment(Environment::Local));
+        assert_ok!(EmailProvider::SendGrid.check_environment(Environment::Production));
+    }
 }
@@ -0,0 +1,198 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::ExecutionOutcome;
+use crate::routes::send_confirmation_email;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+use tera::Tera;
+use tracing::field::display;
+use tracing::Span;
+use uuid::Uuid;
+
+// A confirmation email that keeps failing is dropped once it has been retried this many times.
+const MAX_RETRIES: i16 = 5;
+
+struct ConfirmationTask {
+    subscription_token: String,
+    n_retries: i16,
+    subscriber_email: String,
+    still_pending: bool,
+}
+
+pub async fn run_worker_until_stopped(
+    db_pool: PgPool,
+    email_client: Arc<EmailClient>,
+    templates: Arc<Tera>,
+    base_url: String,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&db_pool, &email_client, &templates, &base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "Enqueuing a confirmation email", skip_all)]
+pub async fn enqueue_confirmation_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+    subscriber_id: &Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO confirmation_email_queue (subscription_token, subscriber_id) VALUES ($1, $2)
+        "#,
+        subscription_token,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Executing a confirmation email task",
+    skip_all,
+    fields(subscriber_email = tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    db_pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Tera,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let (mut transaction, task) = match dequeue_task(db_pool).await? {
+        Some(dequeued) => dequeued,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+    Span::current().record("subscriber_email", &display(&task.subscriber_email));
+
+    if !task.still_pending {
+        tracing::info!("Skipping a subscriber who is no longer waiting for a confirmation.");
+        delete_task(&mut transaction, &task).await?;
+    } else {
+        match SubscriberEmail::parse(task.subscriber_email.clone()) {
+            Ok(email) => {
+                let outcome = send_confirmation_email(
+                    email_client,
+                    &email,
+                    &task.subscription_token,
+                    base_url,
+                    templates,
+                )
+                .await;
+                match outcome {
+                    Ok(()) => delete_task(&mut transaction, &task).await?,
+                    Err(error) => {
+                        tracing::error!(
+                            error.cause_chain = ?error,
+                            error.message = %error,
+                            "Failed to send a queued confirmation email."
+                        );
+                        retry_or_drop_task(&mut transaction, &task).await?;
+                    }
+                }
+            }
+            Err(error) => {
+                tracing::warn!(
+                    error.cause_chain = ?error,
+                    "Skipping a subscriber. Their stored email address is invalid."
+                );
+                delete_task(&mut transaction, &task).await?;
+            }
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the changes to the confirmation email queue.")?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+#[tracing::instrument(name = "Dequeuing a confirmation email task", skip_all)]
+async fn dequeue_task(
+    db_pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, ConfirmationTask)>, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let task = sqlx::query_as!(
+        ConfirmationTask,
+        r#"
+        SELECT q.subscription_token, q.n_retries, s.email AS subscriber_email,
+            (s.status = 'PENDING' AND t.consumed_at IS NULL AND t.expires_at > now())
+                AS "still_pending!"
+        FROM confirmation_email_queue q
+        JOIN subscription_tokens t ON t.subscription_token = q.subscription_token
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE q.execute_after <= now()
+        LIMIT 1
+        FOR UPDATE OF q
+        SKIP LOCKED
+        "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to dequeue a confirmation email task.")?;
+
+    Ok(task.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(name = "Deleting a confirmation email task", skip_all)]
+async fn delete_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &ConfirmationTask,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM confirmation_email_queue WHERE subscription_token = $1
+        "#,
+        task.subscription_token
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to delete a confirmation email task.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Rescheduling a confirmation email task", skip_all, fields(n_retries = task.n_retries))]
+async fn retry_or_drop_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &ConfirmationTask,
+) -> Result<(), anyhow::Error> {
+    if task.n_retries >= MAX_RETRIES {
+        tracing::error!("Giving up on the confirmation email, the task has run out of retries.");
+        return delete_task(transaction, task).await;
+    }
+
+    let execute_after =
+        Utc::now() + chrono::Duration::seconds(30 * 2_i64.pow(task.n_retries as u32));
+    sqlx::query!(
+        r#"
+        UPDATE confirmation_email_queue
+        SET n_retries = n_retries + 1, execute_after = $2
+        WHERE subscription_token = $1
+        "#,
+        task.subscription_token,
+        execute_after
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to reschedule a confirmation email task.")?;
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeliveryFrequency {
+    EveryIssue,
+    Weekly,
+    Monthly,
+}
+
+impl DeliveryFrequency {
+    pub const ALL: [DeliveryFrequency; 3] = [Self::EveryIssue, Self::Weekly, Self::Monthly];
+
+    pub fn parse(s: &str) -> Result<DeliveryFrequency, String> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|frequency| frequency.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid delivery frequency.", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EveryIssue => "EVERY_ISSUE",
+            Self::Weekly => "WEEKLY",
+            Self::Monthly => "MONTHLY",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::EveryIssue => "Every issue",
+            Self::Weekly => "Weekly digest",
+            Self::Monthly => "Monthly digest",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::DeliveryFrequency;
+    use claim::assert_err;
+
+    #[test]
+    fn every_frequency_parses_back_from_its_string() {
+        for frequency in DeliveryFrequency::ALL {
+            assert_eq!(DeliveryFrequency::parse(frequency.as_str()), Ok(frequency));
+        }
+    }
+
+    #[test]
+    fn unknown_frequency_is_rejected() {
+        assert_err!(DeliveryFrequency::parse("DAILY"));
+        assert_err!(DeliveryFrequency::parse("weekly"));
+    }
+}
@@ -1,9 +1,13 @@
+mod delivery_frequency;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+mod subscription_status;
 mod subscription_token;
 
+pub use delivery_frequency::DeliveryFrequency;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscription_status::SubscriptionStatus;
 pub use subscription_token::SubscriptionToken;
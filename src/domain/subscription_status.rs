@@ -0,0 +1,52 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubscriptionStatus {
+    Pending,
+    Confirmed,
+    Unsubscribed,
+    Suppressed,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 4] = [
+        Self::Pending,
+        Self::Confirmed,
+        Self::Unsubscribed,
+        Self::Suppressed,
+    ];
+
+    pub fn parse(s: &str) -> Result<SubscriptionStatus, String> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid subscription status.", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "PENDING",
+            Self::Confirmed => "CONFIRMED",
+            Self::Unsubscribed => "UNSUBSCRIBED",
+            Self::Suppressed => "SUPPRESSED",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriptionStatus;
+    use claim::assert_err;
+
+    #[test]
+    fn every_status_parses_back_from_its_string() {
+        for status in SubscriptionStatus::ALL {
+            assert_eq!(SubscriptionStatus::parse(status.as_str()), Ok(status));
+        }
+    }
+
+    #[test]
+    fn unknown_status_is_rejected() {
+        assert_err!(SubscriptionStatus::parse("DELETED"));
+        assert_err!(SubscriptionStatus::parse("confirmed"));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::{EmailMessage, EmailTransport, TransportError};
+use rand::Rng;
+use std::time::Duration;
+use tracing::Instrument;
+
+pub struct EmailClient {
+    transport: Box<dyn EmailTransport>,
+    sender: SubscriberEmail,
+    retry_policy: RetryPolicy,
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: Duration,
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .checked_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+            .unwrap_or(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0..=self.jitter.as_millis() as u64);
+        exponential.min(self.max_delay) + Duration::from_millis(jitter)
+    }
+
+    /// A `Retry-After` beyond `max_delay` gives up instead - the caller holds a request, or a
+    /// locked queue row, for as long as we sleep.
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_delay => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+}
+
+impl EmailClient {
+    pub fn new(
+        transport: Box<dyn EmailTransport>,
+        sender: SubscriberEmail,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            transport,
+            sender,
+            retry_policy,
+        }
+    }
+
+    pub async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), TransportError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    #[tracing::instrument(
+        name = "Sending email",
+        skip(self, html_content, text_content, headers)
+    )]
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(), TransportError> {
+        let message = EmailMessage {
+            sender: &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        };
+
+        let mut attempt = 1;
+        loop {
+            let outcome = self
+                .transport
+                .send(&message)
+                .instrument(tracing::info_span!("Email delivery attempt", attempt))
+                .await;
+            let (error, delay) = match outcome {
+                Ok(()) => return Ok(()),
+                Err(TransportError::Transient {
+                    source,
+                    retry_after,
+                }) if attempt < self.retry_policy.max_attempts => {
+                    match self.retry_policy.delay(attempt, retry_after) {
+                        Some(delay) => (source, delay),
+                        None => {
+                            return Err(TransportError::Transient {
+                                source,
+                                retry_after,
+                            })
+                        }
+                    }
+                }
+                Err(error) => return Err(error),
+            };
+
+            tracing::warn!(
+                error.message = %error,
+                attempt,
+                retry_in_millis = delay.as_millis() as u64,
+                "Sending email failed, retrying."
+            );
+            actix_web::rt::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, RetryPolicy, SendGridTransport};
+    use claim::{assert_err, assert_ok};
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::Paragraph;
+    use fake::faker::lorem::en::Sentence;
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::any;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_returns_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_keeps_returning_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn sent_email_times_out_if_the_server_keeps_taking_too_long() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_the_server_recovers_within_the_attempts() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_is_not_retried_if_the_server_returns_400() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_waits_for_retry_after_if_the_server_returns_429() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client_with(
+            mock_server.uri(),
+            RetryPolicy {
+                max_delay: Duration::from_secs(2),
+                ..retry_policy()
+            },
+        );
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let started_at = Instant::now();
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_ok!(outcome);
+        assert!(started_at.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn send_email_gives_up_if_the_server_asks_to_wait_longer_than_the_max_delay() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "86400"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let started_at = Instant::now();
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        assert_err!(outcome);
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn retry_after_is_honoured_up_to_the_max_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: Duration::ZERO,
+        };
+
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(5))),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(86400))), None);
+        assert_eq!(policy.delay(2, None), Some(Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_max_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            jitter: Duration::ZERO,
+        };
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(64), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn backoff_jitter_stays_within_bounds() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            jitter: Duration::from_millis(50),
+        };
+
+        for _ in 0..100 {
+            let delay = policy.backoff(1);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(150));
+        }
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+            jitter: Duration::from_millis(5),
+        }
+    }
+
+    fn email_client(base_url: String) -> EmailClient {
+        email_client_with(base_url, retry_policy())
+    }
+
+    fn email_client_with(base_url: String, retry_policy: RetryPolicy) -> EmailClient {
+        let transport = SendGridTransport::new(
+            base_url,
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+        );
+        EmailClient::new(Box::new(transport), email(), retry_policy)
+    }
+}
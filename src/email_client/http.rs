@@ -0,0 +1,49 @@
+use crate::email_client::TransportError;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use std::time::Duration;
+
+pub(super) fn request_error(error: reqwest::Error) -> TransportError {
+    if error.is_timeout() || error.is_connect() || error.is_request() {
+        TransportError::Transient {
+            source: error.into(),
+            retry_after: None,
+        }
+    } else {
+        TransportError::Permanent(error.into())
+    }
+}
+
+pub(super) fn check_response(response: Response) -> Result<(), TransportError> {
+    let status = response.status();
+    let error = match response.error_for_status_ref() {
+        Ok(_) => return Ok(()),
+        Err(error) => error,
+    };
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        Err(TransportError::Transient {
+            source: error.into(),
+            retry_after: parse_retry_after(&response),
+        })
+    } else if status.is_server_error() {
+        Err(TransportError::Transient {
+            source: error.into(),
+            retry_after: None,
+        })
+    } else {
+        Err(TransportError::Permanent(error.into()))
+    }
+}
+
+fn parse_retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+        .or(Some(Duration::ZERO))
+}
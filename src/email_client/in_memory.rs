@@ -0,0 +1,94 @@
+use crate::email_client::{EmailMessage, EmailTransport, TransportError};
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CapturedEmail {
+    pub id: Uuid,
+    pub captured_at: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Clone, Default)]
+pub struct Mailbox(Arc<Mutex<Vec<CapturedEmail>>>);
+
+impl Mailbox {
+    pub fn messages(&self) -> Vec<CapturedEmail> {
+        self.0.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    fn push(&self, email: CapturedEmail) {
+        self.0.lock().unwrap().push(email);
+    }
+}
+
+pub struct InMemoryTransport {
+    mailbox: Mailbox,
+}
+
+impl InMemoryTransport {
+    pub fn new(mailbox: Mailbox) -> Self {
+        Self { mailbox }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for InMemoryTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), TransportError> {
+        self.mailbox.push(CapturedEmail {
+            id: Uuid::new_v4(),
+            captured_at: Utc::now(),
+            from: message.sender.as_ref().to_string(),
+            to: message.recipient.as_ref().to_string(),
+            subject: message.subject.to_string(),
+            html_content: message.html_content.to_string(),
+            text_content: message.text_content.to_string(),
+            headers: owned_headers(message.headers),
+        });
+        Ok(())
+    }
+}
+
+pub(super) fn owned_headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailMessage, EmailTransport, InMemoryTransport, Mailbox};
+
+    #[tokio::test]
+    async fn sent_emails_are_listed_in_the_mailbox_most_recent_first() {
+        let mailbox = Mailbox::default();
+        let transport = InMemoryTransport::new(mailbox.clone());
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("ursula_le_guin@gmail.com".into()).unwrap();
+
+        for subject in ["first", "second"] {
+            transport
+                .send(&EmailMessage {
+                    sender: &sender,
+                    recipient: &recipient,
+                    subject,
+                    html_content: "<p>html content</p>",
+                    text_content: "text content",
+                    headers: &[],
+                })
+                .await
+                .unwrap();
+        }
+
+        let subjects: Vec<_> = mailbox.messages().into_iter().map(|m| m.subject).collect();
+        assert_eq!(subjects, vec!["second", "first"]);
+    }
+}
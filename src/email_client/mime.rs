@@ -0,0 +1,67 @@
+use crate::email_client::EmailMessage;
+use anyhow::Context;
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+
+pub(super) fn build_message(message: &EmailMessage<'_>) -> Result<Message, anyhow::Error> {
+    let from: Mailbox = message
+        .sender
+        .as_ref()
+        .parse()
+        .context("Invalid sender address.")?;
+    let to: Mailbox = message
+        .recipient
+        .as_ref()
+        .parse()
+        .context("Invalid recipient address.")?;
+
+    let mut builder = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(message.subject);
+    for (name, value) in message.headers {
+        let name = HeaderName::new_from_ascii(name.to_string())
+            .with_context(|| format!("Invalid email header name: {}.", name))?;
+        builder = builder.raw_header(HeaderValue::new(name, value.to_string()));
+    }
+
+    builder
+        .multipart(MultiPart::alternative_plain_html(
+            message.text_content.to_string(),
+            message.html_content.to_string(),
+        ))
+        .context("Failed to build the email message.")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::mime::build_message;
+    use crate::email_client::EmailMessage;
+    use claim::assert_ok;
+
+    #[test]
+    fn message_carries_both_the_plain_text_and_the_html_part() {
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("ursula_le_guin@gmail.com".into()).unwrap();
+
+        let message = build_message(&EmailMessage {
+            sender: &sender,
+            recipient: &recipient,
+            subject: "Welcome!",
+            html_content: "<p>html content</p>",
+            text_content: "text content",
+            headers: &[("List-Unsubscribe-Post", "List-Unsubscribe=One-Click")],
+        });
+
+        assert_ok!(&message);
+        let formatted = String::from_utf8(message.unwrap().formatted()).unwrap();
+        assert!(formatted.contains("To: ursula_le_guin@gmail.com"));
+        assert!(formatted.contains("Subject: Welcome!"));
+        assert!(formatted.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(formatted.contains("multipart/alternative"));
+        assert!(formatted.contains("text content"));
+        assert!(formatted.contains("<p>html content</p>"));
+    }
+}
@@ -0,0 +1,19 @@
+mod client;
+mod http;
+mod in_memory;
+mod mime;
+mod outbox;
+mod postmark;
+mod sendgrid;
+mod sendgrid_events;
+mod smtp;
+mod transport;
+
+pub use client::{EmailClient, RetryPolicy};
+pub use in_memory::{CapturedEmail, InMemoryTransport, Mailbox};
+pub use outbox::{OutboxEntry, OutboxTransport};
+pub use postmark::PostmarkTransport;
+pub use sendgrid::SendGridTransport;
+pub use sendgrid_events::SendGridEventVerifier;
+pub use smtp::SmtpTransport;
+pub use transport::{EmailMessage, EmailTransport, TransportError};
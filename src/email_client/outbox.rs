@@ -0,0 +1,117 @@
+use crate::email_client::in_memory::owned_headers;
+use crate::email_client::mime::build_message;
+use crate::email_client::{EmailMessage, EmailTransport, TransportError};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+pub struct OutboxTransport {
+    directory: PathBuf,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct OutboxEntry {
+    pub id: Uuid,
+    pub sent_at: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl OutboxTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    async fn write(&self, message: &EmailMessage<'_>) -> Result<(), anyhow::Error> {
+        let entry = OutboxEntry {
+            id: Uuid::new_v4(),
+            sent_at: Utc::now(),
+            from: message.sender.as_ref().to_string(),
+            to: message.recipient.as_ref().to_string(),
+            subject: message.subject.to_string(),
+            html_content: message.html_content.to_string(),
+            text_content: message.text_content.to_string(),
+            headers: owned_headers(message.headers),
+        };
+        // Prefixing the file names with the timestamp keeps the outbox listing chronological.
+        let file_stem = format!("{}-{}", entry.sent_at.format("%Y%m%dT%H%M%S%.6f"), entry.id);
+        let eml = build_message(message)?.formatted();
+        let sidecar =
+            serde_json::to_vec_pretty(&entry).context("Failed to serialize the email.")?;
+
+        tokio::fs::create_dir_all(&self.directory)
+            .await
+            .with_context(|| format!("Failed to create the outbox at {:?}.", self.directory))?;
+        tokio::fs::write(self.directory.join(format!("{}.eml", file_stem)), eml)
+            .await
+            .context("Failed to write the email to the outbox.")?;
+        tokio::fs::write(self.directory.join(format!("{}.json", file_stem)), sidecar)
+            .await
+            .context("Failed to write the email details to the outbox.")?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for OutboxTransport {
+    #[tracing::instrument(name = "Writing email to the outbox", skip_all)]
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), TransportError> {
+        self.write(message).await.map_err(TransportError::Permanent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailMessage, EmailTransport, OutboxEntry, OutboxTransport};
+    use claim::assert_ok;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn every_email_is_written_as_eml_file_with_json_sidecar() {
+        let directory = std::env::temp_dir().join(format!("outbox-{}", Uuid::new_v4()));
+        let transport = OutboxTransport::new(&directory);
+        let sender = SubscriberEmail::parse("sender@example.com".into()).unwrap();
+        let recipient = SubscriberEmail::parse("ursula_le_guin@gmail.com".into()).unwrap();
+
+        let outcome = transport
+            .send(&EmailMessage {
+                sender: &sender,
+                recipient: &recipient,
+                subject: "Welcome!",
+                html_content: "<p>html content</p>",
+                text_content: "text content",
+                headers: &[],
+            })
+            .await;
+
+        assert_ok!(outcome);
+        let mut files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        assert_eq!(files[1].extension().unwrap(), "json");
+        assert_eq!(files[0].file_stem(), files[1].file_stem());
+
+        let eml = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(eml.contains("Subject: Welcome!"));
+        let sidecar: OutboxEntry =
+            serde_json::from_slice(&std::fs::read(&files[1]).unwrap()).unwrap();
+        assert_eq!(sidecar.to, "ursula_le_guin@gmail.com");
+        assert_eq!(sidecar.subject, "Welcome!");
+        assert_eq!(sidecar.text_content, "text content");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
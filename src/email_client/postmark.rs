@@ -0,0 +1,136 @@
+use crate::email_client::http::{check_response, request_error};
+use crate::email_client::{EmailMessage, EmailTransport, TransportError};
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+use std::time::Duration;
+
+pub struct PostmarkTransport {
+    http_client: Client,
+    base_url: String,
+    server_token: Secret<String>,
+}
+
+impl PostmarkTransport {
+    pub fn new(base_url: String, server_token: Secret<String>, timeout: Duration) -> Self {
+        Self {
+            http_client: Client::builder().timeout(timeout).build().unwrap(),
+            base_url,
+            server_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), TransportError> {
+        let url = format!("{}/email", self.base_url);
+        let request_body = SendEmailRequest {
+            from: message.sender.as_ref(),
+            to: message.recipient.as_ref(),
+            subject: message.subject,
+            html_body: message.html_content,
+            text_body: message.text_content,
+            message_stream: "outbound",
+            headers: message
+                .headers
+                .iter()
+                .map(|(name, value)| Header { name, value })
+                .collect(),
+        };
+
+        let response = self
+            .http_client
+            .post(url)
+            .header("X-Postmark-Server-Token", self.server_token.expose_secret())
+            .header("Accept", "application/json")
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(request_error)?;
+
+        check_response(response)
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    message_stream: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<Header<'a>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Header<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailMessage, EmailTransport, PostmarkTransport};
+    use claim::assert_ok;
+    use fake::faker::internet::en::SafeEmail;
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use std::time::Duration;
+    use wiremock::matchers::{header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                ["From", "To", "Subject", "HtmlBody", "TextBody"]
+                    .iter()
+                    .all(|field| body.get(field).is_some())
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_sends_expected_request() {
+        let mock_server = MockServer::start().await;
+        let transport = PostmarkTransport::new(
+            mock_server.uri(),
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+        );
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let recipient = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = transport
+            .send(&EmailMessage {
+                sender: &sender,
+                recipient: &recipient,
+                subject: "subject",
+                html_content: "<p>content</p>",
+                text_content: "content",
+                headers: &[],
+            })
+            .await;
+
+        assert_ok!(outcome);
+    }
+}
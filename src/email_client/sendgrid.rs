@@ -1,62 +1,55 @@
-use crate::domain::SubscriberEmail;
+use crate::email_client::http::{check_response, request_error};
+use crate::email_client::{EmailMessage, EmailTransport, TransportError};
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
 use std::time::Duration;
 
-pub struct EmailClient {
+pub struct SendGridTransport {
     http_client: Client,
     base_url: String,
-    sender: SubscriberEmail,
     api_key: Secret<String>,
 }
 
-impl EmailClient {
-    pub fn new(
-        base_url: String,
-        sender: SubscriberEmail,
-        api_key: Secret<String>,
-        timeout: Duration,
-    ) -> Self {
+impl SendGridTransport {
+    pub fn new(base_url: String, api_key: Secret<String>, timeout: Duration) -> Self {
         Self {
             http_client: Client::builder().timeout(timeout).build().unwrap(),
             base_url,
-            sender,
             api_key,
         }
     }
+}
 
-    #[tracing::instrument(name = "Sending email", skip(self, html_content, text_content))]
-    pub async fn send_email(
-        &self,
-        recipient: &SubscriberEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+#[async_trait::async_trait]
+impl EmailTransport for SendGridTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), TransportError> {
         let url = format!("{}/v3/mail/send", self.base_url);
         let request_body = SendEmailRequestBody {
             personalizations: vec![EmailPersonalization {
                 to: vec![EmailAddress {
-                    email: self.sender.as_ref(),
+                    email: message.recipient.as_ref(),
                 }],
             }],
             from: EmailAddress {
-                email: recipient.as_ref(),
+                email: message.sender.as_ref(),
             },
-            subject,
+            subject: message.subject,
             content: vec![
                 EmailContent {
                     content_type: "text/plain",
-                    value: text_content,
+                    value: message.text_content,
                 },
                 EmailContent {
                     content_type: "text/html",
-                    value: html_content,
+                    value: message.html_content,
                 },
             ],
+            headers: message.headers.iter().copied().collect(),
         };
 
-        self.http_client
+        let response = self
+            .http_client
             .post(url)
             .header(
                 "Authorization",
@@ -64,10 +57,10 @@ impl EmailClient {
             )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .map_err(request_error)?;
 
-        Ok(())
+        check_response(response)
     }
 }
 
@@ -77,6 +70,8 @@ struct SendEmailRequestBody<'a> {
     from: EmailAddress<'a>,
     subject: &'a str,
     content: Vec<EmailContent<'a>>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<&'a str, &'a str>,
 }
 
 #[derive(serde::Serialize)]
@@ -99,11 +94,9 @@ struct EmailContent<'a> {
 #[cfg(test)]
 mod tests {
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
-    use claim::{assert_err, assert_ok};
+    use crate::email_client::{EmailMessage, EmailTransport, SendGridTransport, TransportError};
+    use claim::{assert_matches, assert_ok};
     use fake::faker::internet::en::SafeEmail;
-    use fake::faker::lorem::en::Paragraph;
-    use fake::faker::lorem::en::Sentence;
     use fake::{Fake, Faker};
     use jsonpath_lib::selector;
     use secrecy::Secret;
@@ -112,103 +105,97 @@ mod tests {
     use wiremock::{Mock, MockServer, Request, ResponseTemplate};
 
     #[tokio::test]
-    async fn send_email_succeeds_if_the_server_returns_200() {
+    async fn send_sends_expected_request() {
         let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
+        let transport = transport(mock_server.uri());
+        let (sender, recipient) = (email(), email());
 
-        Mock::given(any())
+        Mock::given(header_exists("authorization"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/v3/mail/send"))
+            .and(method("POST"))
+            .and(SendEmailRequestMatcher {
+                sender: sender.as_ref().to_string(),
+                recipient: recipient.as_ref().to_string(),
+            })
             .respond_with(ResponseTemplate::new(200))
             .expect(1)
             .mount(&mock_server)
             .await;
 
-        let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
+        let outcome = transport.send(&message(&sender, &recipient)).await;
 
         assert_ok!(outcome);
     }
 
     #[tokio::test]
-    async fn send_email_fails_if_the_server_returns_500() {
+    async fn too_many_requests_are_a_transient_failure() {
         let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
+        let transport = transport(mock_server.uri());
+        let (sender, recipient) = (email(), email());
 
         Mock::given(any())
-            .respond_with(ResponseTemplate::new(500))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "7"))
             .expect(1)
             .mount(&mock_server)
             .await;
 
-        let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
+        let outcome = transport.send(&message(&sender, &recipient)).await;
 
-        assert_err!(outcome);
+        assert_matches!(
+            outcome,
+            Err(TransportError::Transient { retry_after: Some(retry_after), .. })
+                if retry_after == Duration::from_secs(7)
+        );
     }
 
     #[tokio::test]
-    async fn sent_email_times_out_if_the_server_takes_too_long() {
+    async fn bad_requests_are_a_permanent_failure() {
         let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
+        let transport = transport(mock_server.uri());
+        let (sender, recipient) = (email(), email());
 
         Mock::given(any())
-            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(180)))
+            .respond_with(ResponseTemplate::new(400))
             .expect(1)
             .mount(&mock_server)
             .await;
 
-        let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
+        let outcome = transport.send(&message(&sender, &recipient)).await;
 
-        assert_err!(outcome);
-    }
-
-    #[tokio::test]
-    async fn send_email_sends_expected_request() {
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(header_exists("authorization"))
-            .and(header("Content-Type", "application/json"))
-            .and(path("/v3/mail/send"))
-            .and(method("POST"))
-            .and(SendEmailRequestMatcher)
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let _ = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
-
-        // asserts
-    }
-
-    fn subject() -> String {
-        Sentence(1..2).fake()
-    }
-
-    fn content() -> String {
-        Paragraph(1..10).fake()
+        assert_matches!(outcome, Err(TransportError::Permanent(_)));
     }
 
     fn email() -> SubscriberEmail {
         SubscriberEmail::parse(SafeEmail().fake()).unwrap()
     }
 
-    fn email_client(base_url: String) -> EmailClient {
-        EmailClient::new(
+    fn message<'a>(
+        sender: &'a SubscriberEmail,
+        recipient: &'a SubscriberEmail,
+    ) -> EmailMessage<'a> {
+        EmailMessage {
+            sender,
+            recipient,
+            subject: "subject",
+            html_content: "<p>content</p>",
+            text_content: "content",
+            headers: &[],
+        }
+    }
+
+    fn transport(base_url: String) -> SendGridTransport {
+        SendGridTransport::new(
             base_url,
-            email(),
             Secret::new(Faker.fake()),
             Duration::from_millis(200),
         )
     }
 
-    struct SendEmailRequestMatcher;
+    struct SendEmailRequestMatcher {
+        sender: String,
+        recipient: String,
+    }
 
     impl wiremock::Match for SendEmailRequestMatcher {
         fn matches(&self, request: &Request) -> bool {
@@ -217,11 +204,11 @@ mod tests {
             if let Ok(body) = result {
                 let has_subscriber_email = match selector(&body)("$.personalizations.*.to.*.email")
                 {
-                    Ok(res) => !res.is_empty(),
+                    Ok(res) => res.len() == 1 && res[0] == self.recipient.as_str(),
                     Err(_) => false,
                 };
                 let has_sender_email = match selector(&body)("$.from.email") {
-                    Ok(res) => !res.is_empty(),
+                    Ok(res) => res.len() == 1 && res[0] == self.sender.as_str(),
                     Err(_) => false,
                 };
                 let has_subject = match selector(&body)("$.subject") {
@@ -0,0 +1,127 @@
+use anyhow::Context;
+use chrono::Utc;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+
+const MAX_TIMESTAMP_SKEW_SECONDS: i64 = 5 * 60;
+
+pub struct SendGridEventVerifier {
+    key: VerifyingKey,
+}
+
+impl SendGridEventVerifier {
+    pub fn new(verification_key: &str) -> Result<Self, anyhow::Error> {
+        let der = base64::decode(verification_key.trim())
+            .context("The SendGrid verification key is not base64 encoded.")?;
+        let key = VerifyingKey::from_public_key_der(&der)
+            .context("The SendGrid verification key is not an ECDSA P-256 public key.")?;
+        Ok(Self { key })
+    }
+
+    pub fn verify(
+        &self,
+        timestamp: &str,
+        body: &[u8],
+        signature: &str,
+    ) -> Result<(), anyhow::Error> {
+        let signature =
+            base64::decode(signature).context("The signature is not base64 encoded.")?;
+        let signature =
+            Signature::from_der(&signature).context("The signature is not DER encoded.")?;
+        let mut payload = timestamp.as_bytes().to_vec();
+        payload.extend_from_slice(body);
+        self.key
+            .verify(&payload, &signature)
+            .context("The signature does not match the payload.")?;
+
+        let timestamp: i64 = timestamp
+            .parse()
+            .context("The timestamp is not a number of seconds.")?;
+        let skew = Utc::now()
+            .timestamp()
+            .saturating_sub(timestamp)
+            .saturating_abs();
+        if skew > MAX_TIMESTAMP_SKEW_SECONDS {
+            anyhow::bail!("The request was signed {} seconds away from now.", skew);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::email_client::SendGridEventVerifier;
+    use chrono::Utc;
+    use claim::{assert_err, assert_ok};
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::{Signature, SigningKey};
+    use p256::pkcs8::EncodePublicKey;
+
+    fn verification_key(signing_key: &SigningKey) -> String {
+        let der = signing_key.verifying_key().to_public_key_der().unwrap();
+        base64::encode(der.as_bytes())
+    }
+
+    fn seconds_ago(seconds: i64) -> String {
+        (Utc::now().timestamp() - seconds).to_string()
+    }
+
+    fn sign(signing_key: &SigningKey, timestamp: &str, body: &str) -> String {
+        let signature: Signature = signing_key.sign(format!("{}{}", timestamp, body).as_bytes());
+        base64::encode(signature.to_der().as_bytes())
+    }
+
+    #[test]
+    fn signed_payload_is_accepted() {
+        let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+        let verifier = SendGridEventVerifier::new(&verification_key(&signing_key)).unwrap();
+        let timestamp = seconds_ago(0);
+        let signature = sign(&signing_key, &timestamp, "[]");
+
+        assert_ok!(verifier.verify(&timestamp, b"[]", &signature));
+    }
+
+    #[test]
+    fn tampered_payload_or_timestamp_is_rejected() {
+        let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+        let verifier = SendGridEventVerifier::new(&verification_key(&signing_key)).unwrap();
+        let timestamp = seconds_ago(1);
+        let signature = sign(&signing_key, &timestamp, "[]");
+
+        assert_err!(verifier.verify(&timestamp, b"[{}]", &signature));
+        assert_err!(verifier.verify(&seconds_ago(0), b"[]", &signature));
+        assert_err!(verifier.verify(&timestamp, b"[]", "not-a-signature"));
+    }
+
+    #[test]
+    fn payload_signed_with_another_key_is_rejected() {
+        let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+        let other_key = SigningKey::random(&mut rand::rngs::OsRng);
+        let verifier = SendGridEventVerifier::new(&verification_key(&signing_key)).unwrap();
+        let timestamp = seconds_ago(0);
+
+        assert_err!(verifier.verify(&timestamp, b"[]", &sign(&other_key, &timestamp, "[]")));
+    }
+
+    #[test]
+    fn payload_signed_long_ago_or_in_the_future_is_rejected() {
+        let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+        let verifier = SendGridEventVerifier::new(&verification_key(&signing_key)).unwrap();
+
+        for timestamp in [
+            seconds_ago(10 * 60),
+            seconds_ago(-10 * 60),
+            i64::MIN.to_string(),
+        ] {
+            let signature = sign(&signing_key, &timestamp, "[]");
+            assert_err!(verifier.verify(&timestamp, b"[]", &signature));
+        }
+    }
+
+    #[test]
+    fn malformed_verification_key_is_rejected() {
+        assert!(SendGridEventVerifier::new("not a key").is_err());
+        assert!(SendGridEventVerifier::new(&base64::encode("not a key")).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::configuration::{SmtpSettings, SmtpTls};
+use crate::email_client::mime::build_message;
+use crate::email_client::{EmailMessage, EmailTransport, TransportError};
+use anyhow::Context;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use secrecy::ExposeSecret;
+use std::time::Duration;
+
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(settings: &SmtpSettings, timeout: Duration) -> Result<Self, anyhow::Error> {
+        let mut builder = match settings.tls {
+            SmtpTls::Wrapper => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)
+                .context("Failed to set up a TLS connection to the SMTP relay.")?,
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)
+                    .context("Failed to set up a STARTTLS connection to the SMTP relay.")?
+            }
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+            }
+        };
+        builder = builder.port(settings.port).timeout(Some(timeout));
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                password.expose_secret().clone(),
+            ));
+        }
+
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), TransportError> {
+        let email = build_message(message).map_err(TransportError::Permanent)?;
+
+        self.mailer.send(email).await.map_err(|error| {
+            // 5xx replies are final, anything else (4xx replies, connection problems, timeouts)
+            // may go away on its own.
+            if error.is_permanent() {
+                TransportError::Permanent(error.into())
+            } else {
+                TransportError::Transient {
+                    source: error.into(),
+                    retry_after: None,
+                }
+            }
+        })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::domain::SubscriberEmail;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct EmailMessage<'a> {
+    pub sender: &'a SubscriberEmail,
+    pub recipient: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TransportError {
+    #[error("The email provider failed to accept the email, the request can be retried.")]
+    Transient {
+        #[source]
+        source: anyhow::Error,
+        retry_after: Option<Duration>,
+    },
+    #[error("The email provider rejected the email.")]
+    Permanent(#[source] anyhow::Error),
+}
+
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<(), TransportError>;
+}
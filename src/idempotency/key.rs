@@ -0,0 +1,52 @@
+use std::convert::TryFrom;
+
+const MAX_KEY_LENGTH: usize = 50;
+
+#[derive(Debug)]
+pub struct IdempotencyKey(String);
+
+impl TryFrom<String> for IdempotencyKey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.is_empty() {
+            return Err("The idempotency key cannot be empty.".into());
+        }
+        if s.len() >= MAX_KEY_LENGTH {
+            return Err(format!(
+                "The idempotency key must be shorter than {} characters.",
+                MAX_KEY_LENGTH
+            ));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for IdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::idempotency::IdempotencyKey;
+    use claim::{assert_err, assert_ok};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn empty_key_is_invalid() {
+        assert_err!(IdempotencyKey::try_from("".to_string()));
+    }
+
+    #[test]
+    fn key_of_50_characters_or_more_is_invalid() {
+        assert_err!(IdempotencyKey::try_from("a".repeat(50)));
+    }
+
+    #[test]
+    fn uuid_is_a_valid_key() {
+        let key = uuid::Uuid::new_v4().to_string();
+        assert_ok!(IdempotencyKey::try_from(key));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::idempotency::IdempotencyKey;
+use actix_web::body::to_bytes;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum NextAction {
+    StartProcessing(Transaction<'static, Postgres>),
+    ReturnSavedResponse(HttpResponse),
+}
+
+/// The returned transaction locks the inserted row: a concurrent request with the same key waits
+/// on its own insert, then replays the saved response.
+#[tracing::instrument(name = "Try processing an idempotent request", skip(db_pool))]
+pub async fn try_processing(
+    db_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let n_inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        Utc::now()
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to claim the idempotency key.")?
+    .rows_affected();
+
+    if n_inserted_rows > 0 {
+        Ok(NextAction::StartProcessing(transaction))
+    } else {
+        let saved_response = get_saved_response(db_pool, idempotency_key, user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it."))?;
+        Ok(NextAction::ReturnSavedResponse(saved_response))
+    }
+}
+
+#[tracing::instrument(name = "Get saved response", skip(db_pool))]
+async fn get_saved_response(
+    db_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<HttpResponse>, anyhow::Error> {
+    let saved_response = sqlx::query!(
+        r#"
+        SELECT
+            response_status_code as "response_status_code!",
+            response_headers as "response_headers!: Json<Vec<HeaderPairRecord>>",
+            response_body as "response_body!"
+        FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to fetch the saved response.")?;
+
+    match saved_response {
+        Some(r) => {
+            let status_code = StatusCode::from_u16(u16::try_from(r.response_status_code)?)?;
+            let mut response = HttpResponse::build(status_code);
+            for HeaderPairRecord { name, value } in r.response_headers.0 {
+                response.append_header((name, value));
+            }
+            Ok(Some(response.body(r.response_body)))
+        }
+        None => Ok(None),
+    }
+}
+
+#[tracing::instrument(name = "Save response", skip(transaction, http_response))]
+pub async fn save_response(
+    mut transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: HttpResponse,
+) -> Result<HttpResponse, anyhow::Error> {
+    let (response_head, body) = http_response.into_parts();
+    let body = to_bytes(body)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .context("Failed to read the response body.")?;
+    let status_code = response_head.status().as_u16() as i16;
+    let headers: Vec<HeaderPairRecord> = response_head
+        .headers()
+        .iter()
+        .map(|(name, value)| HeaderPairRecord {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect();
+    let headers =
+        serde_json::to_value(headers).context("Failed to serialize the response headers.")?;
+
+    sqlx::query!(
+        r#"
+        UPDATE idempotency
+        SET
+            response_status_code = $3,
+            response_headers = $4,
+            response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to save the response.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to save the response.")?;
+
+    Ok(response_head.set_body(body).map_into_boxed_body())
+}
@@ -0,0 +1,575 @@
+use crate::click_tracking::rewrite_links;
+use crate::domain::{DeliveryFrequency, SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::email_client::EmailClient;
+use crate::subscriber_links::SubscriberLinks;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use std::time::Duration;
+use tera::Tera;
+use tracing::field::display;
+use tracing::Span;
+use uuid::Uuid;
+
+// A task that keeps failing is dropped once it has been retried this many times.
+const MAX_RETRIES: i16 = 5;
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+struct DeliveryTask {
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    n_retries: i16,
+    subscriber_email: String,
+    subscriber_name: String,
+    subscriber_status: String,
+    subscriber_frequency: String,
+    unsubscribe_token: String,
+    tracking_enabled: bool,
+}
+
+struct Delivery {
+    task: DeliveryTask,
+    delivery_id: Uuid,
+    issue: NewsletterIssue,
+    html_content: String,
+    links: IssueLinks,
+}
+
+#[derive(serde::Serialize)]
+struct DigestEntry<'a> {
+    title: &'a str,
+    html_newsletter: &'a str,
+    text_newsletter: &'a str,
+    view_in_browser_link: &'a str,
+    open_tracking_pixel: &'a Option<String>,
+}
+
+struct Subscriber {
+    name: SubscriberName,
+    email: SubscriberEmail,
+}
+
+pub struct IssueLinks {
+    pub unsubscribe: String,
+    pub view_in_browser: String,
+    pub preferences: String,
+    pub open_tracking_pixel: Option<String>,
+}
+
+pub struct RenderedIssue {
+    pub html: String,
+    pub text: String,
+}
+
+struct NewsletterIssue {
+    slug: String,
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+/// Tasks are locked with `FOR UPDATE SKIP LOCKED` while delivered, so any number of workers can
+/// drain the same queue without sending an issue twice to the same subscriber.
+pub async fn run_worker_until_stopped(
+    db_pool: PgPool,
+    email_client: Arc<EmailClient>,
+    templates: Arc<Tera>,
+    links: Arc<SubscriberLinks>,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&db_pool, &email_client, &templates, &links).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "Enqueuing delivery tasks", skip(transaction))]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_id, execute_after)
+        SELECT DISTINCT il.newsletter_issue_id, m.subscriber_id, GREATEST(now(), CASE s.frequency
+            WHEN 'WEEKLY' THEN s.last_delivered_at + interval '7 days'
+            WHEN 'MONTHLY' THEN s.last_delivered_at + interval '1 month'
+        END)
+        FROM newsletter_issue_lists il
+        JOIN list_memberships m ON m.list_id = il.list_id
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE il.newsletter_issue_id = $1
+            AND m.status = 'CONFIRMED'
+            AND s.status = 'CONFIRMED'
+        "#,
+        newsletter_issue_id
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Executing a delivery task",
+    skip_all,
+    fields(
+        newsletter_issue_id = tracing::field::Empty,
+        subscriber_email = tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_task(
+    db_pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &Tera,
+    links: &SubscriberLinks,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let (mut transaction, task) = match dequeue_task(db_pool).await? {
+        Some(dequeued) => dequeued,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+    Span::current()
+        .record("newsletter_issue_id", &display(task.newsletter_issue_id))
+        .record("subscriber_email", &display(&task.subscriber_email));
+
+    if task.subscriber_status != SubscriptionStatus::Confirmed.as_str() {
+        tracing::info!("Skipping a subscriber who is no longer confirmed.");
+        delete_task(&mut transaction, &task).await?;
+        commit(transaction).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    let mut tasks = Vec::new();
+    if task.subscriber_frequency != DeliveryFrequency::EveryIssue.as_str() {
+        if let Some(due_at) = next_digest_due_at(&mut transaction, task.subscriber_id).await? {
+            // Received a digest since the issue was queued, it waits for the next one.
+            postpone_task(&mut transaction, &task, due_at).await?;
+            commit(transaction).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+        tasks = dequeue_digest_tasks(&mut transaction, &task).await?;
+    }
+    tasks.insert(0, task);
+
+    let subscriber = match parse_subscriber(&tasks[0]) {
+        Ok(subscriber) => subscriber,
+        Err(error) => {
+            tracing::warn!(
+                error.cause_chain = ?error,
+                "Skipping a confirmed subscriber. Their stored contact details are invalid."
+            );
+            for task in &tasks {
+                delete_task(&mut transaction, task).await?;
+            }
+            commit(transaction).await?;
+            return Ok(ExecutionOutcome::TaskCompleted);
+        }
+    };
+    let mut deliveries = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let issue = get_issue(db_pool, task.newsletter_issue_id).await?;
+        deliveries.push(prepare_delivery(task, issue, links));
+    }
+    deliveries.sort_by_key(|delivery| delivery.issue.published_at);
+
+    match send_deliveries(&deliveries, &subscriber, email_client, templates).await {
+        Ok(()) => {
+            for delivery in &deliveries {
+                record_delivery(&mut transaction, &delivery.task, delivery.delivery_id).await?;
+                delete_task(&mut transaction, &delivery.task).await?;
+            }
+        }
+        Err(error) => {
+            tracing::error!(
+                error.cause_chain = ?error,
+                error.message = %error,
+                "Failed to deliver issue to a confirmed subscriber."
+            );
+            for delivery in &deliveries {
+                retry_or_drop_task(&mut transaction, &delivery.task).await?;
+            }
+        }
+    }
+    commit(transaction).await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+fn prepare_delivery(
+    task: DeliveryTask,
+    issue: NewsletterIssue,
+    links: &SubscriberLinks,
+) -> Delivery {
+    let delivery_id = Uuid::new_v4();
+    let issue_links = IssueLinks {
+        unsubscribe: links.unsubscribe(&task.unsubscribe_token),
+        view_in_browser: links.view_in_browser(&issue.slug),
+        preferences: links.preferences(task.subscriber_id),
+        open_tracking_pixel: task
+            .tracking_enabled
+            .then(|| links.open_tracking_pixel(delivery_id)),
+    };
+    let html_content = if task.tracking_enabled {
+        rewrite_links(&issue.html_content, |url| {
+            links.click_tracking(delivery_id, url)
+        })
+    } else {
+        issue.html_content.clone()
+    };
+    Delivery {
+        task,
+        delivery_id,
+        issue,
+        html_content,
+        links: issue_links,
+    }
+}
+
+fn parse_subscriber(task: &DeliveryTask) -> Result<Subscriber, anyhow::Error> {
+    let name =
+        SubscriberName::parse(task.subscriber_name.clone()).map_err(|e| anyhow::anyhow!(e))?;
+    let email =
+        SubscriberEmail::parse(task.subscriber_email.clone()).map_err(|e| anyhow::anyhow!(e))?;
+    Ok(Subscriber { name, email })
+}
+
+#[tracing::instrument(name = "Dequeuing a delivery task", skip_all)]
+async fn dequeue_task(
+    db_pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, DeliveryTask)>, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let task = sqlx::query_as!(
+        DeliveryTask,
+        r#"
+        SELECT q.newsletter_issue_id, q.subscriber_id, q.n_retries,
+            s.email AS subscriber_email, s.name AS subscriber_name,
+            s.status AS subscriber_status, s.frequency AS subscriber_frequency,
+            s.unsubscribe_token,
+            NOT EXISTS(
+                SELECT 1 FROM newsletter_issue_lists il
+                JOIN lists l ON l.list_id = il.list_id
+                WHERE il.newsletter_issue_id = q.newsletter_issue_id AND NOT l.tracking_enabled
+            ) AS "tracking_enabled!"
+        FROM issue_delivery_queue q
+        JOIN subscriptions s ON s.id = q.subscriber_id
+        WHERE q.execute_after <= now()
+        LIMIT 1
+        FOR UPDATE OF q
+        SKIP LOCKED
+        "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to dequeue a delivery task.")?;
+
+    Ok(task.map(|task| (transaction, task)))
+}
+
+#[tracing::instrument(name = "Dequeuing the rest of a digest", skip_all)]
+async fn dequeue_digest_tasks(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+) -> Result<Vec<DeliveryTask>, anyhow::Error> {
+    let tasks = sqlx::query_as!(
+        DeliveryTask,
+        r#"
+        SELECT q.newsletter_issue_id, q.subscriber_id, q.n_retries,
+            s.email AS subscriber_email, s.name AS subscriber_name,
+            s.status AS subscriber_status, s.frequency AS subscriber_frequency,
+            s.unsubscribe_token,
+            NOT EXISTS(
+                SELECT 1 FROM newsletter_issue_lists il
+                JOIN lists l ON l.list_id = il.list_id
+                WHERE il.newsletter_issue_id = q.newsletter_issue_id AND NOT l.tracking_enabled
+            ) AS "tracking_enabled!"
+        FROM issue_delivery_queue q
+        JOIN subscriptions s ON s.id = q.subscriber_id
+        WHERE q.subscriber_id = $1 AND q.newsletter_issue_id <> $2 AND q.execute_after <= now()
+        FOR UPDATE OF q
+        SKIP LOCKED
+        "#,
+        task.subscriber_id,
+        task.newsletter_issue_id
+    )
+    .fetch_all(transaction)
+    .await
+    .context("Failed to dequeue the rest of a digest.")?;
+
+    Ok(tasks)
+}
+
+/// The subscriber stays locked until the delivery is recorded, a worker picking up another of
+/// their issues meanwhile waits and then finds the digest delivered.
+#[tracing::instrument(name = "Checking when the next digest is due", skip(transaction))]
+async fn next_digest_due_at(
+    transaction: &mut Transaction<'static, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let due_at = sqlx::query!(
+        r#"
+        SELECT CASE frequency
+            WHEN 'WEEKLY' THEN last_delivered_at + interval '7 days'
+            WHEN 'MONTHLY' THEN last_delivered_at + interval '1 month'
+        END AS due_at
+        FROM subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        subscriber_id
+    )
+    .fetch_one(transaction)
+    .await
+    .context("Failed to check when the next digest is due.")?
+    .due_at;
+
+    Ok(due_at.filter(|due_at| *due_at > Utc::now()))
+}
+
+#[tracing::instrument(name = "Postponing a delivery task", skip(transaction, task))]
+async fn postpone_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+    execute_after: DateTime<Utc>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET execute_after = $3
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        execute_after
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to postpone a delivery task.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Deleting a delivery task", skip_all)]
+async fn delete_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to delete a delivery task.")?;
+
+    Ok(())
+}
+
+async fn commit(transaction: Transaction<'static, Postgres>) -> Result<(), anyhow::Error> {
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the changes to the delivery queue.")
+}
+
+#[tracing::instrument(name = "Recording a delivery", skip(transaction, task))]
+async fn record_delivery(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+    delivery_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries
+            (delivery_id, newsletter_issue_id, subscriber_id, delivered_at, tracked)
+        VALUES ($1, $2, $3, now(), $4)
+        "#,
+        delivery_id,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        task.tracking_enabled
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the delivery.")?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET last_delivered_at = now() WHERE id = $1
+        "#,
+        task.subscriber_id
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to record the delivery to the subscriber.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Rescheduling a delivery task", skip_all, fields(n_retries = task.n_retries))]
+async fn retry_or_drop_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &DeliveryTask,
+) -> Result<(), anyhow::Error> {
+    if task.n_retries >= MAX_RETRIES {
+        tracing::error!("Giving up on delivering the issue, the task has run out of retries.");
+        return delete_task(transaction, task).await;
+    }
+
+    let execute_after =
+        Utc::now() + chrono::Duration::seconds(30 * 2_i64.pow(task.n_retries as u32));
+    sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET n_retries = n_retries + 1, execute_after = $3
+        WHERE newsletter_issue_id = $1 AND subscriber_id = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_id,
+        execute_after
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to reschedule a delivery task.")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Getting a newsletter issue", skip(db_pool))]
+async fn get_issue(db_pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT slug, title, text_content, html_content, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(db_pool)
+    .await
+    .context("Failed to fetch the newsletter issue.")?;
+
+    Ok(issue)
+}
+
+#[tracing::instrument(
+    name = "Sending newsletter issue to confirmed subscriber",
+    skip_all,
+    fields(
+        subscriber_email = %subscriber.email,
+        n_issues = deliveries.len()
+    )
+)]
+async fn send_deliveries(
+    deliveries: &[Delivery],
+    subscriber: &Subscriber,
+    email_client: &EmailClient,
+    templates: &Tera,
+) -> Result<(), anyhow::Error> {
+    let (subject, rendered) = match deliveries {
+        [delivery] => (
+            delivery.issue.title.clone(),
+            render_issue(
+                templates,
+                subscriber.name.as_ref(),
+                &delivery.html_content,
+                &delivery.issue.text_content,
+                &delivery.links,
+            )?,
+        ),
+        _ => (
+            format!("Your digest: {} new issues", deliveries.len()),
+            render_digest(templates, subscriber.name.as_ref(), deliveries)?,
+        ),
+    };
+    // RFC 8058 - lets mail clients offer a one-click unsubscribe button.
+    let list_unsubscribe = format!("<{}>", deliveries[0].links.unsubscribe);
+    let headers = [
+        ("List-Unsubscribe", list_unsubscribe.as_str()),
+        ("List-Unsubscribe-Post", "List-Unsubscribe=One-Click"),
+    ];
+
+    email_client
+        .send_email_with_headers(
+            &subscriber.email,
+            subject.as_str(),
+            rendered.html.as_str(),
+            rendered.text.as_str(),
+            &headers,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Sending newsletter email failed for email address: {}",
+                subscriber.email.as_ref()
+            )
+        })
+}
+
+pub fn render_issue(
+    templates: &Tera,
+    subscriber_name: &str,
+    html_content: &str,
+    text_content: &str,
+    links: &IssueLinks,
+) -> Result<RenderedIssue, tera::Error> {
+    let mut context = tera::Context::new();
+    context.insert("subscriber_name", subscriber_name);
+    context.insert("html_newsletter", html_content);
+    context.insert("text_newsletter", text_content);
+    context.insert("unsubscribe_link", &links.unsubscribe);
+    context.insert("view_in_browser_link", &links.view_in_browser);
+    context.insert("preferences_link", &links.preferences);
+    context.insert("open_tracking_pixel", &links.open_tracking_pixel);
+
+    Ok(RenderedIssue {
+        html: templates.render("newsletters/distribute_newsletter.html", &context)?,
+        text: templates.render("newsletters/distribute_newsletter.txt", &context)?,
+    })
+}
+
+fn render_digest(
+    templates: &Tera,
+    subscriber_name: &str,
+    deliveries: &[Delivery],
+) -> Result<RenderedIssue, tera::Error> {
+    let issues: Vec<DigestEntry> = deliveries
+        .iter()
+        .map(|delivery| DigestEntry {
+            title: &delivery.issue.title,
+            html_newsletter: &delivery.html_content,
+            text_newsletter: &delivery.issue.text_content,
+            view_in_browser_link: &delivery.links.view_in_browser,
+            open_tracking_pixel: &delivery.links.open_tracking_pixel,
+        })
+        .collect();
+    let mut context = tera::Context::new();
+    context.insert("subscriber_name", subscriber_name);
+    context.insert("issues", &issues);
+    context.insert("unsubscribe_link", &deliveries[0].links.unsubscribe);
+    context.insert("preferences_link", &deliveries[0].links.preferences);
+
+    Ok(RenderedIssue {
+        html: templates.render("newsletters/distribute_digest.html", &context)?,
+        text: templates.render("newsletters/distribute_digest.txt", &context)?,
+    })
+}
@@ -1,7 +1,17 @@
 #![allow(clippy::toplevel_ref_arg)]
+pub mod authentication;
+pub mod click_tracking;
 pub mod configuration;
+pub mod confirmation_email_worker;
 pub mod domain;
 pub mod email_client;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod magic_link;
+pub mod newsletter_scheduler;
+pub mod rate_limit;
 pub mod routes;
+pub mod session;
 pub mod startup;
+pub mod subscriber_links;
 pub mod telemetry;
@@ -0,0 +1,173 @@
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum MagicLinkError {
+    #[error("The link is not valid.")]
+    Invalid,
+    #[error("The link has expired.")]
+    Expired,
+}
+
+pub struct MagicLinkSigner {
+    key: Secret<String>,
+}
+
+impl MagicLinkSigner {
+    pub fn new(key: Secret<String>) -> Self {
+        Self { key }
+    }
+
+    /// The `purpose` is signed too, so a token issued for one kind of link is useless for another.
+    pub fn sign(&self, purpose: &str, subject: &str, expires_at: DateTime<Utc>) -> String {
+        let payload = format!("{}:{}", expires_at.timestamp(), subject);
+        let signature = self.mac(purpose, &payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            base64::encode_config(&payload, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    pub fn verify(&self, purpose: &str, token: &str) -> Result<String, MagicLinkError> {
+        let (payload, signature) = token.split_once('.').ok_or(MagicLinkError::Invalid)?;
+        let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|payload| String::from_utf8(payload).ok())
+            .ok_or(MagicLinkError::Invalid)?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| MagicLinkError::Invalid)?;
+        self.mac(purpose, &payload)
+            .verify_slice(&signature)
+            .map_err(|_| MagicLinkError::Invalid)?;
+
+        let (expires_at, subject) = payload.split_once(':').ok_or(MagicLinkError::Invalid)?;
+        let expires_at = expires_at
+            .parse()
+            .ok()
+            .and_then(|expires_at| Utc.timestamp_opt(expires_at, 0).single())
+            .ok_or(MagicLinkError::Invalid)?;
+        if expires_at <= Utc::now() {
+            return Err(MagicLinkError::Expired);
+        }
+        Ok(subject.to_string())
+    }
+
+    fn mac(&self, purpose: &str, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length.");
+        mac.update(purpose.as_bytes());
+        mac.update(b"\0");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::magic_link::{MagicLinkError, MagicLinkSigner};
+    use chrono::{Duration, Utc};
+    use claim::assert_err;
+    use hmac::Mac;
+    use secrecy::Secret;
+
+    fn signer() -> MagicLinkSigner {
+        MagicLinkSigner::new(Secret::new("a-very-secret-key".into()))
+    }
+
+    #[test]
+    fn signed_subject_is_returned_by_verify() {
+        let token = signer().sign(
+            "preferences",
+            "subscriber-42",
+            Utc::now() + Duration::hours(1),
+        );
+
+        assert_eq!(
+            signer().verify("preferences", &token),
+            Ok("subscriber-42".to_string())
+        );
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let token = signer().sign(
+            "preferences",
+            "subscriber-42",
+            Utc::now() - Duration::hours(1),
+        );
+
+        assert_eq!(
+            signer().verify("preferences", &token),
+            Err(MagicLinkError::Expired)
+        );
+    }
+
+    #[test]
+    fn token_signed_for_another_purpose_is_rejected() {
+        let token = signer().sign("export", "subscriber-42", Utc::now() + Duration::hours(1));
+
+        assert_eq!(
+            signer().verify("preferences", &token),
+            Err(MagicLinkError::Invalid)
+        );
+    }
+
+    #[test]
+    fn tampered_token_is_rejected() {
+        let token = signer().sign(
+            "preferences",
+            "subscriber-42",
+            Utc::now() + Duration::hours(1),
+        );
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged_payload = base64::encode_config(
+            format!(
+                "{}:subscriber-43",
+                (Utc::now() + Duration::hours(1)).timestamp()
+            ),
+            base64::URL_SAFE_NO_PAD,
+        );
+
+        assert_err!(signer().verify("preferences", &format!("{}.{}", forged_payload, signature)));
+        assert_err!(signer().verify("preferences", "garbage"));
+    }
+
+    #[test]
+    fn token_signed_with_another_key_is_rejected() {
+        let other_signer = MagicLinkSigner::new(Secret::new("another-key".into()));
+        let token = other_signer.sign(
+            "preferences",
+            "subscriber-42",
+            Utc::now() + Duration::hours(1),
+        );
+
+        assert_eq!(
+            signer().verify("preferences", &token),
+            Err(MagicLinkError::Invalid)
+        );
+    }
+
+    #[test]
+    fn token_expiring_out_of_range_is_rejected() {
+        let payload = format!("{}:subscriber-42", i64::MAX);
+        let signature = signer()
+            .mac("preferences", &payload)
+            .finalize()
+            .into_bytes();
+        let token = format!(
+            "{}.{}",
+            base64::encode_config(&payload, base64::URL_SAFE_NO_PAD),
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        );
+
+        assert_eq!(
+            signer().verify("preferences", &token),
+            Err(MagicLinkError::Invalid)
+        );
+    }
+}
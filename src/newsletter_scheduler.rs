@@ -0,0 +1,77 @@
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::field::display;
+use tracing::Span;
+use uuid::Uuid;
+
+pub enum PublishOutcome {
+    IssuePublished,
+    NothingDue,
+}
+
+pub async fn run_scheduler_until_stopped(db_pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        match try_publish_due_issue(&db_pool).await {
+            Ok(PublishOutcome::NothingDue) => {
+                actix_web::rt::time::sleep(Duration::from_secs(10)).await;
+            }
+            Ok(PublishOutcome::IssuePublished) => {}
+            Err(_) => {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Publishing a scheduled issue",
+    skip_all,
+    fields(newsletter_issue_id = tracing::field::Empty),
+    err
+)]
+pub async fn try_publish_due_issue(db_pool: &PgPool) -> Result<PublishOutcome, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE status = 'SCHEDULED' AND published_at <= now()
+        ORDER BY published_at
+        LIMIT 1
+        FOR UPDATE
+        SKIP LOCKED
+        "#,
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to fetch a due newsletter issue.")?;
+    let newsletter_issue_id: Uuid = match issue {
+        Some(issue) => issue.newsletter_issue_id,
+        None => return Ok(PublishOutcome::NothingDue),
+    };
+    Span::current().record("newsletter_issue_id", &display(newsletter_issue_id));
+
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues SET status = 'PUBLISHED' WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to mark the newsletter issue as published.")?;
+    enqueue_delivery_tasks(&mut transaction, newsletter_issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit the publication of a scheduled issue.")?;
+
+    Ok(PublishOutcome::IssuePublished)
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+pub struct TokenBucket {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BucketState {
+    pub tokens: f64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+impl TokenBucket {
+    pub fn full(&self, now: DateTime<Utc>) -> BucketState {
+        BucketState {
+            tokens: self.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    pub fn take(&self, state: &BucketState, now: DateTime<Utc>) -> (BucketState, Decision) {
+        let elapsed = (now - state.updated_at).to_std().unwrap_or_default();
+        let tokens =
+            (state.tokens + elapsed.as_secs_f64() * self.refill_rate()).min(self.capacity as f64);
+
+        if tokens >= 1.0 {
+            let state = BucketState {
+                tokens: tokens - 1.0,
+                updated_at: now,
+            };
+            (state, Decision::Allowed)
+        } else {
+            let state = BucketState {
+                tokens,
+                updated_at: now,
+            };
+            let retry_after = Duration::from_secs_f64((1.0 - tokens) / self.refill_rate());
+            (state, Decision::Limited { retry_after })
+        }
+    }
+
+    pub fn full_at(&self, state: &BucketState) -> DateTime<Utc> {
+        let missing_tokens = self.capacity as f64 - state.tokens;
+        let until_full = Duration::from_secs_f64(missing_tokens.max(0.0) / self.refill_rate());
+        state.updated_at
+            + chrono::Duration::from_std(until_full)
+                .unwrap_or_else(|_| chrono::Duration::max_value())
+    }
+
+    fn refill_rate(&self) -> f64 {
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_limit::{Decision, TokenBucket};
+    use chrono::Utc;
+    use std::time::Duration;
+
+    fn bucket() -> TokenBucket {
+        TokenBucket {
+            capacity: 2,
+            period: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn requests_are_allowed_until_the_bucket_is_empty() {
+        let bucket = bucket();
+        let now = Utc::now();
+
+        let (state, first) = bucket.take(&bucket.full(now), now);
+        let (state, second) = bucket.take(&state, now);
+        let (_, third) = bucket.take(&state, now);
+
+        assert_eq!(first, Decision::Allowed);
+        assert_eq!(second, Decision::Allowed);
+        assert_eq!(
+            third,
+            Decision::Limited {
+                retry_after: Duration::from_secs(30)
+            }
+        );
+    }
+
+    #[test]
+    fn the_bucket_is_refilled_over_time() {
+        let bucket = bucket();
+        let now = Utc::now();
+        let (state, _) = bucket.take(&bucket.full(now), now);
+        let (state, _) = bucket.take(&state, now);
+
+        let (_, decision) = bucket.take(&state, now + chrono::Duration::seconds(30));
+
+        assert_eq!(decision, Decision::Allowed);
+    }
+
+    #[test]
+    fn the_bucket_never_holds_more_than_its_capacity() {
+        let bucket = bucket();
+        let now = Utc::now();
+
+        let (state, _) = bucket.take(&bucket.full(now), now + chrono::Duration::hours(1));
+
+        assert_eq!(state.tokens, 1.0);
+        assert_eq!(
+            bucket.full_at(&state),
+            state.updated_at + chrono::Duration::seconds(30)
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::rate_limit::{Decision, RateLimiter};
+use crate::routes::ApiError;
+use actix_web::body::MessageBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::Error;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+pub struct RateLimitMiddleware {
+    limiter: RateLimiter,
+}
+
+impl RateLimitMiddleware {
+    pub fn per_client_ip(limiter: RateLimiter) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimitMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = InnerRateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(InnerRateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct InnerRateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service<ServiceRequest> for InnerRateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            // The peer address rather than `X-Forwarded-For`, which any client can forge.
+            if let Some(peer_addr) = req.peer_addr() {
+                let key = format!("ip:{}", peer_addr.ip());
+                let decision = limiter
+                    .check(&key)
+                    .await
+                    .map_err(ErrorInternalServerError)?;
+                if let Decision::Limited { retry_after } = decision {
+                    tracing::warn!(client_ip = %peer_addr.ip(), "Rate limiting a client.");
+                    return Err(ApiError::RateLimited { retry_after }.into());
+                }
+            }
+
+            service.call(req).await
+        })
+    }
+}
@@ -0,0 +1,28 @@
+mod bucket;
+mod middleware;
+mod store;
+
+pub use bucket::{BucketState, Decision, TokenBucket};
+pub use middleware::RateLimitMiddleware;
+pub use store::{
+    delete_full_buckets, run_cleanup_until_stopped, InMemoryRateLimitStore, PostgresRateLimitStore,
+    RateLimitStore,
+};
+
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    bucket: TokenBucket,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, bucket: TokenBucket) -> Self {
+        Self { store, bucket }
+    }
+
+    pub async fn check(&self, key: &str) -> Result<Decision, anyhow::Error> {
+        self.store.acquire(key, &self.bucket).await
+    }
+}
@@ -0,0 +1,146 @@
+use crate::rate_limit::{BucketState, Decision, TokenBucket};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A bucket back to its full capacity is as good as a missing one, stores are free to forget it.
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn acquire(&self, key: &str, bucket: &TokenBucket) -> Result<Decision, anyhow::Error>;
+}
+
+pub struct PostgresRateLimitStore {
+    db_pool: PgPool,
+}
+
+impl PostgresRateLimitStore {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for PostgresRateLimitStore {
+    #[tracing::instrument(name = "Acquire rate limit token", skip(self, bucket))]
+    async fn acquire(&self, key: &str, bucket: &TokenBucket) -> Result<Decision, anyhow::Error> {
+        let now = Utc::now();
+        let mut transaction = self
+            .db_pool
+            .begin()
+            .await
+            .context("Failed to acquire database connection from the pool.")?;
+        // Creating the bucket upfront lets concurrent requests for a new key queue up on the
+        // row lock below instead of all starting from a full bucket.
+        let full = bucket.full(now);
+        sqlx::query!(
+            r#"
+            INSERT INTO rate_limit_buckets (key, tokens, updated_at, full_at)
+            VALUES ($1, $2, $3, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            key,
+            full.tokens,
+            full.updated_at
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to create the rate limit bucket.")?;
+        let state = sqlx::query_as!(
+            BucketState,
+            r#"
+            SELECT tokens, updated_at FROM rate_limit_buckets WHERE key = $1 FOR UPDATE
+            "#,
+            key
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .context("Failed to load the rate limit bucket.")?;
+
+        let (state, decision) = bucket.take(&state, now);
+        sqlx::query!(
+            r#"
+            UPDATE rate_limit_buckets SET tokens = $2, updated_at = $3, full_at = $4
+            WHERE key = $1
+            "#,
+            key,
+            state.tokens,
+            state.updated_at,
+            bucket.full_at(&state)
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to update the rate limit bucket.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit the rate limit bucket update.")?;
+
+        Ok(decision)
+    }
+}
+
+pub async fn run_cleanup_until_stopped(db_pool: PgPool) -> Result<(), anyhow::Error> {
+    loop {
+        // A failed cleanup is retried on the next round, the buckets are only kept longer.
+        let _ = delete_full_buckets(&db_pool).await;
+        actix_web::rt::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+#[tracing::instrument(name = "Delete full rate limit buckets", skip(db_pool), err)]
+pub async fn delete_full_buckets(db_pool: &PgPool) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!(r#"DELETE FROM rate_limit_buckets WHERE full_at <= now()"#)
+        .execute(db_pool)
+        .await
+        .context("Failed to delete full rate limit buckets.")?;
+    Ok(result.rows_affected())
+}
+
+#[derive(Default)]
+pub struct InMemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, (BucketState, DateTime<Utc>)>>,
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for InMemoryRateLimitStore {
+    async fn acquire(&self, key: &str, bucket: &TokenBucket) -> Result<Decision, anyhow::Error> {
+        let now = Utc::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.retain(|_, (_, full_at)| *full_at > now);
+
+        let state = buckets
+            .get(key)
+            .map(|(state, _)| *state)
+            .unwrap_or_else(|| bucket.full(now));
+        let (state, decision) = bucket.take(&state, now);
+        buckets.insert(key.to_string(), (state, bucket.full_at(&state)));
+
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_limit::{Decision, InMemoryRateLimitStore, RateLimitStore, TokenBucket};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn buckets_are_kept_per_key() {
+        let store = InMemoryRateLimitStore::default();
+        let bucket = TokenBucket {
+            capacity: 1,
+            period: Duration::from_secs(60),
+        };
+
+        let first = store.acquire("ip:10.0.0.1", &bucket).await.unwrap();
+        let second = store.acquire("ip:10.0.0.1", &bucket).await.unwrap();
+        let other_key = store.acquire("ip:10.0.0.2", &bucket).await.unwrap();
+
+        assert_eq!(first, Decision::Allowed);
+        assert!(matches!(second, Decision::Limited { .. }));
+        assert_eq!(other_key, Decision::Allowed);
+    }
+}
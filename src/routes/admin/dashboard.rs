@@ -0,0 +1,44 @@
+use crate::authentication::LoggedInUser;
+use crate::routes::utils::render_html;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use tera::Tera;
+use uuid::Uuid;
+
+#[tracing::instrument(
+    name = "Render admin dashboard",
+    skip(user, db_pool, templates),
+    fields(
+        user_id = %user.user_id
+    )
+)]
+pub async fn admin_dashboard(
+    user: LoggedInUser,
+    db_pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let username = get_username(user.user_id, &db_pool).await?;
+
+    let mut context = tera::Context::new();
+    context.insert("username", &username);
+    render_html(&templates, "admin/dashboard.html", &context)
+}
+
+#[tracing::instrument(name = "Get username", skip(db_pool))]
+pub async fn get_username(user_id: Uuid, db_pool: &PgPool) -> Result<String, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve a username.")?;
+
+    Ok(row.username)
+}
@@ -0,0 +1,22 @@
+use crate::authentication::LoggedInUser;
+use crate::routes::utils::see_other;
+use crate::routes::ApiError;
+use crate::session::{FlashMessage, TypedSession};
+use actix_web::HttpResponse;
+use anyhow::Context;
+
+#[tracing::instrument(
+    name = "Log out",
+    skip(user, session),
+    fields(
+        user_id = %user.user_id
+    )
+)]
+pub async fn log_out(user: LoggedInUser, session: TypedSession) -> Result<HttpResponse, ApiError> {
+    session.log_out();
+    session
+        .add_flash_message(FlashMessage::info("You have successfully logged out."))
+        .context("Failed to store a flash message.")?;
+
+    Ok(see_other("/login"))
+}
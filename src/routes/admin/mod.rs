@@ -0,0 +1,7 @@
+mod dashboard;
+mod logout;
+mod password;
+
+pub use dashboard::*;
+pub use logout::*;
+pub use password::*;
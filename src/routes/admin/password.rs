@@ -0,0 +1,146 @@
+use crate::authentication::{
+    change_password, validate_credentials, AuthError, Credentials, LoggedInUser,
+};
+use crate::routes::admin::get_username;
+use crate::routes::utils::{render_html, see_other};
+use crate::routes::ApiError;
+use crate::session::{FlashMessage, TypedSession};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tera::Tera;
+use unicode_segmentation::UnicodeSegmentation;
+
+const MIN_PASSWORD_LENGTH: usize = 12;
+const MAX_PASSWORD_LENGTH: usize = 128;
+
+#[derive(serde::Deserialize)]
+pub struct ChangePasswordFormData {
+    current_password: Secret<String>,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[tracing::instrument(
+    name = "Render change password form",
+    skip(user, session, templates),
+    fields(
+        user_id = %user.user_id
+    )
+)]
+pub async fn change_password_form(
+    user: LoggedInUser,
+    session: TypedSession,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let flash_messages = session
+        .take_flash_messages()
+        .context("Failed to read flash messages.")?;
+
+    let mut context = tera::Context::new();
+    context.insert("flash_messages", &flash_messages);
+    render_html(&templates, "admin/password.html", &context)
+}
+
+#[tracing::instrument(
+    name = "Change password",
+    skip(form, user, session, db_pool),
+    fields(
+        user_id = %user.user_id
+    )
+)]
+pub async fn change_password_submit(
+    form: web::Form<ChangePasswordFormData>,
+    user: LoggedInUser,
+    session: TypedSession,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.0;
+
+    if let Some(error) = validate_new_password(&form.new_password, &form.new_password_check) {
+        session
+            .add_flash_message(FlashMessage::error(error))
+            .context("Failed to store a flash message.")?;
+        return Ok(see_other("/admin/password"));
+    }
+
+    let username = get_username(user.user_id, &db_pool).await?;
+    let credentials = Credentials {
+        username,
+        password: form.current_password,
+    };
+    match validate_credentials(credentials, &db_pool).await {
+        Ok(_) => {}
+        Err(AuthError::InvalidCredentials(_)) => {
+            session
+                .add_flash_message(FlashMessage::error("The current password is incorrect."))
+                .context("Failed to store a flash message.")?;
+            return Ok(see_other("/admin/password"));
+        }
+        Err(AuthError::UnexpectedError(e)) => return Err(ApiError::UnexpectedError(e)),
+    }
+
+    change_password(user.user_id, form.new_password, &db_pool).await?;
+    session
+        .add_flash_message(FlashMessage::info("Your password has been changed."))
+        .context("Failed to store a flash message.")?;
+
+    Ok(see_other("/admin/password"))
+}
+
+fn validate_new_password(
+    new_password: &Secret<String>,
+    new_password_check: &Secret<String>,
+) -> Option<String> {
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        return Some(
+            "You entered two different new passwords - the field values must match.".into(),
+        );
+    }
+
+    let length = new_password.expose_secret().graphemes(true).count();
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&length) {
+        return Some(format!(
+            "The new password must be between {} and {} characters long.",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::admin::password::validate_new_password;
+    use claim::{assert_none, assert_some};
+    use secrecy::Secret;
+
+    fn secret(s: &str) -> Secret<String> {
+        Secret::new(s.to_string())
+    }
+
+    #[test]
+    fn matching_password_of_valid_length_is_accepted() {
+        let password = "a".repeat(12);
+        assert_none!(validate_new_password(
+            &secret(&password),
+            &secret(&password)
+        ));
+    }
+
+    #[test]
+    fn mismatching_passwords_are_rejected() {
+        assert_some!(validate_new_password(
+            &secret("correct horse battery staple"),
+            &secret("correct horse battery stapler")
+        ));
+    }
+
+    #[test]
+    fn too_short_or_too_long_passwords_are_rejected() {
+        for password in &["a".repeat(11), "a".repeat(129)] {
+            assert_some!(validate_new_password(&secret(password), &secret(password)));
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::email_client::{CapturedEmail, Mailbox};
+use crate::routes::utils::render_html;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use linkify::{LinkFinder, LinkKind};
+use tera::Tera;
+
+#[derive(serde::Serialize)]
+struct MailboxEntry {
+    #[serde(flatten)]
+    email: CapturedEmail,
+    links: Vec<String>,
+}
+
+#[tracing::instrument(name = "Render dev mailbox", skip(mailbox, templates))]
+pub async fn dev_mailbox(
+    mailbox: web::Data<Mailbox>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let entries: Vec<_> = mailbox
+        .messages()
+        .into_iter()
+        .map(|email| MailboxEntry {
+            links: extract_links(&email),
+            email,
+        })
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("emails", &entries);
+    render_html(&templates, "dev/mailbox.html", &context)
+}
+
+fn extract_links(email: &CapturedEmail) -> Vec<String> {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+
+    let mut links: Vec<String> = finder
+        .links(&email.text_content)
+        .chain(finder.links(&email.html_content))
+        .map(|link| link.as_str().to_string())
+        .collect();
+    links.sort();
+    links.dedup();
+    links
+}
@@ -1,12 +1,19 @@
+use crate::authentication::AuthError;
+use actix_web::http::header::{ContentType, RETRY_AFTER, WWW_AUTHENTICATE};
 use actix_web::http::StatusCode;
-use actix_web::ResponseError;
+use actix_web::{HttpResponse, ResponseError};
 use std::fmt::{Debug, Formatter};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error)]
 pub enum ApiError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("Authentication failed.")]
+    AuthenticationError(#[source] anyhow::Error),
+    #[error("Too many requests, try again later.")]
+    RateLimited { retry_after: Duration },
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -15,9 +22,38 @@ impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         match self {
             ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ApiError::AuthenticationError(_) => StatusCode::UNAUTHORIZED,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
             ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            ApiError::AuthenticationError(_) => {
+                response.insert_header((WWW_AUTHENTICATE, r#"Basic realm="publish""#));
+            }
+            ApiError::RateLimited { retry_after } => {
+                // Retry-After only takes whole seconds, rounding down would invite an early retry.
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                response.insert_header((RETRY_AFTER, seconds.to_string()));
+            }
+            _ => {}
+        }
+        response
+            .content_type(ContentType::plaintext())
+            .body(self.to_string())
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::InvalidCredentials(_) => ApiError::AuthenticationError(e.into()),
+            AuthError::UnexpectedError(_) => ApiError::UnexpectedError(e.into()),
+        }
+    }
 }
 
 impl Debug for ApiError {
@@ -26,6 +62,10 @@ impl Debug for ApiError {
     }
 }
 
+pub(crate) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.code().as_deref() == Some("23505"))
+}
+
 #[derive(Error)]
 #[error("A database error occurred while trying to store subscription confirmation token.")]
 pub struct StoreTokenError(#[source] pub sqlx::Error);
@@ -0,0 +1,73 @@
+use crate::routes::utils::render_html;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tera::Tera;
+
+#[derive(serde::Serialize)]
+struct IssueSummary {
+    slug: String,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct Issue {
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Render issue archive", skip(db_pool, templates))]
+pub async fn list_issues(
+    db_pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT slug, title, published_at
+        FROM newsletter_issues
+        WHERE status = 'PUBLISHED'
+        ORDER BY published_at DESC
+        "#
+    )
+    .fetch_all(db_pool.get_ref())
+    .await
+    .context("Failed to fetch the published newsletter issues.")?;
+
+    let mut context = tera::Context::new();
+    context.insert("issues", &issues);
+    render_html(&templates, "issues/list.html", &context)
+}
+
+#[tracing::instrument(name = "Render newsletter issue", skip(db_pool, templates))]
+pub async fn view_issue(
+    slug: web::Path<String>,
+    db_pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let issue = sqlx::query_as!(
+        Issue,
+        r#"
+        SELECT title, html_content, published_at
+        FROM newsletter_issues
+        WHERE slug = $1 AND status = 'PUBLISHED'
+        "#,
+        slug.as_str()
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .context("Failed to fetch the newsletter issue.")?;
+
+    match issue {
+        Some(issue) => {
+            let mut context = tera::Context::new();
+            context.insert("issue", &issue);
+            render_html(&templates, "issues/issue.html", &context)
+        }
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
@@ -0,0 +1,197 @@
+use crate::authentication::AuthenticatedUser;
+use crate::routes::newsletter::slugify;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Serialize)]
+struct MailingList {
+    list_id: Uuid,
+    slug: String,
+    name: String,
+    is_default: bool,
+    tracking_enabled: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewListData {
+    slug: String,
+    name: String,
+    tracking_enabled: Option<bool>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListUpdateData {
+    name: Option<String>,
+    tracking_enabled: Option<bool>,
+}
+
+#[tracing::instrument(name = "Listing mailing lists", skip(db_pool, user), fields(user_id = %user.user_id))]
+pub async fn list_lists(
+    user: AuthenticatedUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let lists = sqlx::query_as!(
+        MailingList,
+        r#"
+        SELECT list_id, slug, name, is_default, tracking_enabled
+        FROM lists
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(db_pool.get_ref())
+    .await
+    .context("Failed to fetch the mailing lists.")?;
+
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+#[tracing::instrument(
+    name = "Creating a mailing list",
+    skip(db_pool, user, list),
+    fields(user_id = %user.user_id, list_slug = %list.slug)
+)]
+pub async fn create_list(
+    user: AuthenticatedUser,
+    list: web::Json<NewListData>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if list.slug.is_empty() || slugify(&list.slug) != list.slug {
+        return Err(ApiError::ValidationError(
+            "A list slug may only contain lowercase letters, digits and single dashes.".into(),
+        ));
+    }
+    if list.name.trim().is_empty() {
+        return Err(ApiError::ValidationError("A list needs a name.".into()));
+    }
+
+    let created = sqlx::query_as!(
+        MailingList,
+        r#"
+        INSERT INTO lists (list_id, slug, name, tracking_enabled)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (slug) DO NOTHING
+        RETURNING list_id, slug, name, is_default, tracking_enabled
+        "#,
+        Uuid::new_v4(),
+        list.slug,
+        list.name.trim(),
+        list.tracking_enabled.unwrap_or(true)
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .context("Failed to store the mailing list.")?;
+
+    match created {
+        Some(created) => Ok(HttpResponse::Created().json(created)),
+        None => Err(ApiError::ValidationError(format!(
+            "A list with the '{}' slug already exists.",
+            list.slug
+        ))),
+    }
+}
+
+#[tracing::instrument(
+    name = "Updating a mailing list",
+    skip(db_pool, user, update),
+    fields(user_id = %user.user_id)
+)]
+pub async fn update_list(
+    user: AuthenticatedUser,
+    slug: web::Path<String>,
+    update: web::Json<ListUpdateData>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let name = update.name.as_deref().map(str::trim);
+    if name == Some("") {
+        return Err(ApiError::ValidationError("A list needs a name.".into()));
+    }
+
+    let updated = sqlx::query_as!(
+        MailingList,
+        r#"
+        UPDATE lists SET
+            name = COALESCE($2, name),
+            tracking_enabled = COALESCE($3, tracking_enabled)
+        WHERE slug = $1
+        RETURNING list_id, slug, name, is_default, tracking_enabled
+        "#,
+        slug.as_str(),
+        name,
+        update.tracking_enabled
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .context("Failed to update the mailing list.")?;
+
+    match updated {
+        Some(updated) => Ok(HttpResponse::Ok().json(updated)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(name = "Resolving mailing lists", skip(transaction))]
+pub(crate) async fn resolve_list_slugs(
+    transaction: &mut Transaction<'_, Postgres>,
+    slugs: &[String],
+) -> Result<Vec<Uuid>, ApiError> {
+    if slugs.is_empty() {
+        return Ok(vec![default_list_id(transaction).await?]);
+    }
+    let lists = sqlx::query!(
+        r#"SELECT list_id, slug FROM lists WHERE slug = ANY($1)"#,
+        slugs
+    )
+    .fetch_all(transaction)
+    .await
+    .context("Failed to fetch the mailing lists.")?;
+
+    if let Some(unknown) = slugs
+        .iter()
+        .find(|slug| !lists.iter().any(|list| &list.slug == *slug))
+    {
+        return Err(ApiError::ValidationError(format!(
+            "There is no '{}' list.",
+            unknown
+        )));
+    }
+    Ok(lists.into_iter().map(|list| list.list_id).collect())
+}
+
+#[tracing::instrument(name = "Validating target mailing lists", skip(transaction))]
+pub(crate) async fn validate_list_ids(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_ids: &[Uuid],
+) -> Result<Vec<Uuid>, ApiError> {
+    if list_ids.is_empty() {
+        return Ok(vec![default_list_id(transaction).await?]);
+    }
+    let known = sqlx::query!(
+        r#"SELECT list_id FROM lists WHERE list_id = ANY($1)"#,
+        list_ids
+    )
+    .fetch_all(transaction)
+    .await
+    .context("Failed to fetch the mailing lists.")?;
+
+    if let Some(unknown) = list_ids
+        .iter()
+        .find(|id| !known.iter().any(|list| &list.list_id == *id))
+    {
+        return Err(ApiError::ValidationError(format!(
+            "There is no list with the '{}' id.",
+            unknown
+        )));
+    }
+    Ok(known.into_iter().map(|list| list.list_id).collect())
+}
+
+async fn default_list_id(transaction: &mut Transaction<'_, Postgres>) -> Result<Uuid, ApiError> {
+    let list = sqlx::query!(r#"SELECT list_id FROM lists WHERE is_default"#)
+        .fetch_one(transaction)
+        .await
+        .context("Failed to fetch the default mailing list.")?;
+    Ok(list.list_id)
+}
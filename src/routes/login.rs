@@ -0,0 +1,68 @@
+use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::routes::utils::{render_html, see_other};
+use crate::routes::ApiError;
+use crate::session::{FlashMessage, TypedSession};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use tera::Tera;
+
+#[derive(serde::Deserialize)]
+pub struct LoginFormData {
+    username: String,
+    password: Secret<String>,
+}
+
+#[tracing::instrument(name = "Render login form", skip(session, templates))]
+pub async fn login_form(
+    session: TypedSession,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let flash_messages = session
+        .take_flash_messages()
+        .context("Failed to read flash messages.")?;
+
+    let mut context = tera::Context::new();
+    context.insert("flash_messages", &flash_messages);
+    render_html(&templates, "admin/login.html", &context)
+}
+
+#[tracing::instrument(
+    name = "Log in",
+    skip(form, db_pool, session),
+    fields(
+        username = %form.username,
+        user_id = tracing::field::Empty
+    )
+)]
+pub async fn login(
+    form: web::Form<LoginFormData>,
+    db_pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, ApiError> {
+    let form = form.0;
+    let credentials = Credentials {
+        username: form.username,
+        password: form.password,
+    };
+
+    match validate_credentials(credentials, &db_pool).await {
+        Ok(user_id) => {
+            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            session.renew();
+            session
+                .insert_user_id(user_id)
+                .context("Failed to store the user id in the session.")?;
+            Ok(see_other("/admin/dashboard"))
+        }
+        Err(AuthError::InvalidCredentials(e)) => {
+            tracing::warn!(error.cause_chain = ?e, "Failed login attempt");
+            session
+                .add_flash_message(FlashMessage::error("Authentication failed."))
+                .context("Failed to store a flash message.")?;
+            Ok(see_other("/login"))
+        }
+        Err(AuthError::UnexpectedError(e)) => Err(ApiError::UnexpectedError(e)),
+    }
+}
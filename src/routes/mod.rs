@@ -1,10 +1,42 @@
+mod admin;
+mod dev_mailbox;
 mod errors;
 mod health_check;
+mod issues;
+mod lists;
+mod login;
 mod newsletter;
+mod newsletter_preview;
+mod scheduled_newsletters;
+mod subscriber_data;
+mod subscribers;
+mod subscribers_csv;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod tracking;
+mod utils;
+mod webhooks;
 
+pub use admin::*;
+pub use dev_mailbox::*;
+pub use errors::ApiError;
 pub use health_check::*;
+pub use issues::*;
+pub use lists::*;
+pub use login::*;
 pub use newsletter::*;
+pub use newsletter_preview::*;
+pub use scheduled_newsletters::*;
+pub use subscriber_data::*;
+pub use subscribers::*;
+pub use subscribers_csv::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data::*;
+pub use subscriptions_preferences::*;
+pub use subscriptions_unsubscribe::*;
+pub use tracking::*;
+pub use webhooks::*;
@@ -1,124 +1,208 @@
-use crate::domain::{SubscriberEmail, SubscriberName};
-use crate::email_client::EmailClient;
+use crate::authentication::AuthenticatedUser;
+use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use crate::issue_delivery_worker::enqueue_delivery_tasks;
 use crate::routes::errors::ApiError;
-use actix_web::{web, HttpResponse};
+use crate::routes::lists::validate_list_ids;
+use actix_web::{web, HttpRequest, HttpResponse};
 use anyhow::Context;
-use sqlx::PgPool;
-use tera::Tera;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::TryInto;
+use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
 pub struct BodyData {
     title: String,
     content: Content,
+    send_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    list_ids: Vec<Uuid>,
 }
 
 #[derive(serde::Deserialize)]
 pub struct Content {
-    text: String,
-    html: String,
-}
-
-pub struct ConfirmedSubscriber {
-    name: SubscriberName,
-    email: SubscriberEmail,
+    pub(crate) text: String,
+    pub(crate) html: String,
 }
 
 #[tracing::instrument(
     name = "Distributing the newsletter",
-    skip(newsletter, db_pool, email_client, templates),
+    skip(request, newsletter, db_pool, user),
     fields(
-        newsletter_title = %newsletter.title
+        newsletter_title = %newsletter.title,
+        user_id = %user.user_id
     ),
 )]
 pub async fn distribute_newsletter(
+    request: HttpRequest,
+    user: AuthenticatedUser,
     newsletter: web::Json<BodyData>,
     db_pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
-    templates: web::Data<Tera>,
 ) -> Result<HttpResponse, ApiError> {
-    let confirmed_subscribers = get_confirmed_subscribers(&db_pool).await?;
-    for subscriber in confirmed_subscribers {
-        match subscriber {
-            Ok(subscriber) => send_newsletter(&newsletter, &subscriber, &email_client, &templates)
-                .await
-                .with_context(|| format!("Failed to send newsletter to {}", subscriber.email))?,
-            Err(error) => {
-                tracing::warn!(error.cause_chain = ?error, "Skipping a confirmed subscriber Their stored contact details are invalid")
-            }
+    let idempotency_key = get_idempotency_key(&request).map_err(ApiError::ValidationError)?;
+    let mut transaction = match try_processing(&db_pool, &idempotency_key, user.user_id).await? {
+        NextAction::StartProcessing(transaction) => transaction,
+        NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+    };
+
+    let issue = match newsletter.send_at {
+        Some(send_at) => NewsletterIssueStatus::scheduled_at(send_at)?,
+        None => NewsletterIssueStatus::published_now(),
+    };
+    let list_ids = validate_list_ids(&mut transaction, &newsletter.list_ids).await?;
+    let issue_id = insert_newsletter_issue(&mut transaction, &newsletter, user.user_id, &issue)
+        .await
+        .context("Failed to store newsletter issue details.")?;
+    store_issue_lists(&mut transaction, issue_id, &list_ids)
+        .await
+        .context("Failed to store the lists targeted by the newsletter issue.")?;
+    if issue.status == "PUBLISHED" {
+        enqueue_delivery_tasks(&mut transaction, issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks.")?;
+    }
+
+    let response = HttpResponse::Accepted().json(serde_json::json!({
+        "newsletter_issue_id": issue_id,
+        "status": issue.status,
+        "published_at": issue.published_at,
+    }));
+    let response = save_response(transaction, &idempotency_key, user.user_id, response).await?;
+    Ok(response)
+}
+
+fn get_idempotency_key(request: &HttpRequest) -> Result<IdempotencyKey, String> {
+    request
+        .headers()
+        .get("Idempotency-Key")
+        .ok_or_else(|| "The 'Idempotency-Key' header is missing.".to_string())?
+        .to_str()
+        .map_err(|_| "The 'Idempotency-Key' header is not a valid string.".to_string())?
+        .to_string()
+        .try_into()
+}
+
+struct NewsletterIssueStatus {
+    status: &'static str,
+    published_at: DateTime<Utc>,
+}
+
+impl NewsletterIssueStatus {
+    fn published_now() -> Self {
+        Self {
+            status: "PUBLISHED",
+            published_at: Utc::now(),
         }
     }
 
-    Ok(HttpResponse::Ok().finish())
+    fn scheduled_at(send_at: DateTime<Utc>) -> Result<Self, ApiError> {
+        validate_send_at(send_at)?;
+        Ok(Self {
+            status: "SCHEDULED",
+            published_at: send_at,
+        })
+    }
+}
+
+pub(crate) fn validate_send_at(send_at: DateTime<Utc>) -> Result<(), ApiError> {
+    if send_at <= Utc::now() {
+        return Err(ApiError::ValidationError(
+            "The 'send_at' timestamp must be in the future.".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Storing newsletter issue", skip_all)]
+async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter: &BodyData,
+    author_id: Uuid,
+    issue: &NewsletterIssueStatus,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let title_slug = slugify(&newsletter.title);
+    let mut slug = title_slug.clone();
+    loop {
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO newsletter_issues (
+                newsletter_issue_id, title, text_content, html_content, published_at, slug,
+                author_id, status
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (slug) DO NOTHING
+            "#,
+            newsletter_issue_id,
+            newsletter.title,
+            newsletter.content.text,
+            newsletter.content.html,
+            issue.published_at,
+            slug,
+            author_id,
+            issue.status
+        )
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            return Ok(newsletter_issue_id);
+        }
+        // Taken, possibly by an issue published at the same time - waiting on the conflicting
+        // row instead of failing on the unique index.
+        slug = format!(
+            "{}-{}",
+            title_slug,
+            &Uuid::new_v4().to_simple().to_string()[..8]
+        );
+    }
 }
 
-#[allow(clippy::unnecessary_unwrap)]
-#[tracing::instrument(name = "Getting confirmed subscribers", skip(db_pool))]
-async fn get_confirmed_subscribers(
-    db_pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let confirmed_subscribers = sqlx::query!(
+#[tracing::instrument(name = "Storing newsletter issue lists", skip(transaction))]
+async fn store_issue_lists(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
         r#"
-        SELECT name, email
-        FROM subscriptions
-        WHERE status = 'CONFIRMED'
+        INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+        SELECT $1, list_id FROM UNNEST($2::uuid[]) AS list_id
         "#,
+        newsletter_issue_id,
+        list_ids
     )
-    .fetch_all(db_pool)
-    .await?
-    .into_iter()
-    .map(|r| {
-        let subscriber_email = SubscriberEmail::parse(r.email).map_err(|e| anyhow::anyhow!(e));
-        let subscriber_name = SubscriberName::parse(r.name).map_err(|e| anyhow::anyhow!(e));
-
-        if subscriber_name.is_ok() && subscriber_email.is_ok() {
-            Ok(ConfirmedSubscriber {
-                name: subscriber_name.unwrap(),
-                email: subscriber_email.unwrap(),
-            })
-        } else if subscriber_name.is_err() {
-            Err(subscriber_name.unwrap_err())
-        } else {
-            Err(subscriber_email.unwrap_err())
-        }
-    })
-    .collect();
+    .execute(transaction)
+    .await?;
 
-    Ok(confirmed_subscribers)
+    Ok(())
 }
 
-#[tracing::instrument(
-    name = "Sending newsletter to confirmed subscriber",
-    skip(newsletter, subscriber, email_client, templates),
-    fields(
-        subscriber_email = %subscriber.email
-    )
-)]
-async fn send_newsletter(
-    newsletter: &BodyData,
-    subscriber: &ConfirmedSubscriber,
-    email_client: &EmailClient,
-    templates: &Tera,
-) -> Result<(), anyhow::Error> {
-    let mut context = tera::Context::new();
-    context.insert("subscriber_name", subscriber.name.as_ref());
-    context.insert("html_newsletter", newsletter.content.html.as_str());
-    context.insert("text_newsletter", newsletter.content.text.as_str());
-
-    let html_body = templates.render("newsletters/distribute_newsletter.html", &context)?;
-    let text_body = templates.render("newsletters/distribute_newsletter.txt", &context)?;
-
-    email_client
-        .send_email(
-            &subscriber.email,
-            newsletter.title.as_str(),
-            html_body.as_str(),
-            text_body.as_str(),
-        )
-        .await
-        .with_context(|| {
-            format!(
-                "Sending newsletter email failed for email address: {}",
-                subscriber.email.as_ref()
-            )
-        })
+pub(crate) fn slugify(title: &str) -> String {
+    let slug = title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "issue".into()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::newsletter::slugify;
+
+    #[test]
+    fn slug_keeps_only_lowercase_alphanumeric_words() {
+        assert_eq!(slugify("Hello, World! Issue #42"), "hello-world-issue-42");
+    }
+
+    #[test]
+    fn slug_of_a_title_without_any_word_is_a_placeholder() {
+        assert_eq!(slugify("?!"), "issue");
+    }
 }
@@ -0,0 +1,129 @@
+use crate::authentication::AuthenticatedUser;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::issue_delivery_worker::{render_issue, IssueLinks, RenderedIssue};
+use crate::routes::newsletter::{slugify, Content};
+use crate::routes::ApiError;
+use crate::startup::ApplicationBaseUrl;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use tera::Tera;
+
+// Stands in for the subscriber the preview and test emails are rendered for.
+const SAMPLE_SUBSCRIBER_NAME: &str = "Jane Doe";
+const MAX_TEST_RECIPIENTS: usize = 10;
+
+#[derive(serde::Deserialize)]
+pub struct PreviewData {
+    title: String,
+    content: Content,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TestSendData {
+    title: String,
+    content: Content,
+    recipients: Vec<String>,
+}
+
+#[tracing::instrument(
+    name = "Previewing a newsletter issue",
+    skip(newsletter, templates, base_url, user),
+    fields(
+        newsletter_title = %newsletter.title,
+        user_id = %user.user_id
+    )
+)]
+pub async fn preview_newsletter(
+    user: AuthenticatedUser,
+    newsletter: web::Json<PreviewData>,
+    templates: web::Data<Tera>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, ApiError> {
+    let rendered = render_sample(
+        &templates,
+        &base_url.0,
+        &newsletter.title,
+        &newsletter.content,
+    )?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "subject": newsletter.title,
+        "html": rendered.html,
+        "text": rendered.text,
+    })))
+}
+
+#[tracing::instrument(
+    name = "Sending a test newsletter issue",
+    skip(newsletter, templates, base_url, email_client, user),
+    fields(
+        newsletter_title = %newsletter.title,
+        user_id = %user.user_id
+    )
+)]
+pub async fn send_test_newsletter(
+    user: AuthenticatedUser,
+    newsletter: web::Json<TestSendData>,
+    templates: web::Data<Tera>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    email_client: web::Data<EmailClient>,
+) -> Result<HttpResponse, ApiError> {
+    let newsletter = newsletter.0;
+    if newsletter.recipients.is_empty() || newsletter.recipients.len() > MAX_TEST_RECIPIENTS {
+        return Err(ApiError::ValidationError(format!(
+            "A test issue must be sent to between 1 and {} recipients.",
+            MAX_TEST_RECIPIENTS
+        )));
+    }
+    let recipients = newsletter
+        .recipients
+        .into_iter()
+        .map(SubscriberEmail::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::ValidationError)?;
+    let rendered = render_sample(
+        &templates,
+        &base_url.0,
+        &newsletter.title,
+        &newsletter.content,
+    )?;
+
+    for recipient in &recipients {
+        email_client
+            .send_email(recipient, &newsletter.title, &rendered.html, &rendered.text)
+            .await
+            .with_context(|| {
+                format!(
+                    "Sending test newsletter email failed for email address: {}",
+                    recipient.as_ref()
+                )
+            })?;
+    }
+
+    let sent_to: Vec<_> = recipients.iter().map(|r| r.as_ref()).collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "sent_to": sent_to })))
+}
+
+fn render_sample(
+    templates: &Tera,
+    base_url: &str,
+    title: &str,
+    content: &Content,
+) -> Result<RenderedIssue, anyhow::Error> {
+    // The links look like the real ones, but they do not point to anything yet.
+    let links = IssueLinks {
+        unsubscribe: format!("{}/subscriptions/unsubscribe?token=preview", base_url),
+        view_in_browser: format!("{}/issues/{}", base_url, slugify(title)),
+        preferences: format!("{}/subscriptions/preferences?token=preview", base_url),
+        open_tracking_pixel: None,
+    };
+    render_issue(
+        templates,
+        SAMPLE_SUBSCRIBER_NAME,
+        &content.html,
+        &content.text,
+        &links,
+    )
+    .context("Failed to render the newsletter issue.")
+}
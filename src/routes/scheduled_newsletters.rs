@@ -0,0 +1,104 @@
+use crate::authentication::AuthenticatedUser;
+use crate::routes::newsletter::validate_send_at;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Serialize)]
+struct ScheduledIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    send_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleData {
+    send_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(
+    name = "Listing scheduled newsletter issues",
+    skip(db_pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn list_scheduled_newsletters(
+    user: AuthenticatedUser,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let issues = sqlx::query_as!(
+        ScheduledIssue,
+        r#"
+        SELECT newsletter_issue_id, title, published_at AS send_at
+        FROM newsletter_issues
+        WHERE status = 'SCHEDULED'
+        ORDER BY published_at
+        "#
+    )
+    .fetch_all(db_pool.get_ref())
+    .await
+    .context("Failed to fetch the scheduled newsletter issues.")?;
+
+    Ok(HttpResponse::Ok().json(issues))
+}
+
+#[tracing::instrument(
+    name = "Rescheduling a newsletter issue",
+    skip(db_pool, user, body),
+    fields(user_id = %user.user_id)
+)]
+pub async fn reschedule_newsletter(
+    user: AuthenticatedUser,
+    newsletter_issue_id: web::Path<Uuid>,
+    body: web::Json<RescheduleData>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    validate_send_at(body.send_at)?;
+    let issue = sqlx::query_as!(
+        ScheduledIssue,
+        r#"
+        UPDATE newsletter_issues SET published_at = $2
+        WHERE newsletter_issue_id = $1 AND status = 'SCHEDULED'
+        RETURNING newsletter_issue_id, title, published_at AS send_at
+        "#,
+        *newsletter_issue_id,
+        body.send_at
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await
+    .context("Failed to reschedule the newsletter issue.")?;
+
+    match issue {
+        Some(issue) => Ok(HttpResponse::Ok().json(issue)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(
+    name = "Cancelling a newsletter issue",
+    skip(db_pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn cancel_newsletter(
+    user: AuthenticatedUser,
+    newsletter_issue_id: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues SET status = 'CANCELLED'
+        WHERE newsletter_issue_id = $1 AND status = 'SCHEDULED'
+        "#,
+        *newsletter_issue_id
+    )
+    .execute(db_pool.get_ref())
+    .await
+    .context("Failed to cancel the newsletter issue.")?;
+
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,339 @@
+use crate::authentication::AuthenticatedUser;
+use crate::domain::SubscriberEmail;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct DataSubjectRequest {
+    email: String,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct SubscriberDataExport {
+    exported_at: DateTime<Utc>,
+    subscription: SubscriptionRecord,
+    list_memberships: Vec<ListMembershipRecord>,
+    subscription_tokens: Vec<SubscriptionTokenRecord>,
+    pending_deliveries: Vec<PendingDeliveryRecord>,
+    deliveries: Vec<DeliveryRecord>,
+    email_opens: Vec<EmailOpenRecord>,
+    link_clicks: Vec<LinkClickRecord>,
+    email_events: Vec<EmailEventRecord>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionRecord {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    frequency: String,
+    subscribed_at: DateTime<Utc>,
+    last_delivered_at: Option<DateTime<Utc>>,
+    unsubscribe_token: String,
+}
+
+#[derive(serde::Serialize)]
+struct ListMembershipRecord {
+    list_slug: String,
+    list_name: String,
+    status: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionTokenRecord {
+    subscription_token: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+    new_email: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct DeliveryRecord {
+    delivery_id: Uuid,
+    newsletter_issue_id: Uuid,
+    title: String,
+    delivered_at: DateTime<Utc>,
+    tracked: bool,
+}
+
+#[derive(serde::Serialize)]
+struct EmailOpenRecord {
+    delivery_id: Uuid,
+    opened_at: DateTime<Utc>,
+    user_agent: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct LinkClickRecord {
+    delivery_id: Uuid,
+    url: String,
+    clicked_at: DateTime<Utc>,
+    user_agent: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct EmailEventRecord {
+    event_type: String,
+    reason: Option<String>,
+    occurred_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct PendingDeliveryRecord {
+    newsletter_issue_id: Uuid,
+    title: String,
+    n_retries: i16,
+    execute_after: DateTime<Utc>,
+}
+
+#[tracing::instrument(
+    name = "Exporting subscriber data",
+    skip(request, user, db_pool),
+    fields(user_id = %user.user_id)
+)]
+pub async fn export_subscriber_data(
+    user: AuthenticatedUser,
+    request: web::Json<DataSubjectRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let email = SubscriberEmail::parse(request.0.email).map_err(ApiError::ValidationError)?;
+    let subscriber_id = match find_subscriber_id(&db_pool, &email).await? {
+        Some(subscriber_id) => subscriber_id,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    match collect_subscriber_data(&db_pool, subscriber_id).await? {
+        Some(export) => Ok(HttpResponse::Ok().json(export)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(
+    name = "Erasing subscriber data",
+    skip(request, user, db_pool),
+    fields(user_id = %user.user_id)
+)]
+pub async fn erase_subscriber_data(
+    user: AuthenticatedUser,
+    request: web::Json<DataSubjectRequest>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let email = SubscriberEmail::parse(request.0.email).map_err(ApiError::ValidationError)?;
+    let subscriber_id = match find_subscriber_id(&db_pool, &email).await? {
+        Some(subscriber_id) => subscriber_id,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    if erase_subscriber(&db_pool, subscriber_id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+/// Ignores the case, like the webhooks do - the exact match wins should both spellings exist.
+#[tracing::instrument(name = "Find subscriber id by email", skip(db_pool, email))]
+pub(crate) async fn find_subscriber_id(
+    db_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let subscriber_id = sqlx::query!(
+        r#"
+        SELECT id FROM subscriptions WHERE lower(email) = lower($1)
+        ORDER BY email = $1 DESC, subscribed_at
+        LIMIT 1
+        "#,
+        email.as_ref()
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to find the subscriber by email.")?
+    .map(|row| row.id);
+
+    Ok(subscriber_id)
+}
+
+#[tracing::instrument(name = "Collect subscriber data", skip(db_pool))]
+pub(crate) async fn collect_subscriber_data(
+    db_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberDataExport>, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let subscription = match get_subscription(&mut transaction, subscriber_id).await? {
+        Some(subscription) => subscription,
+        None => return Ok(None),
+    };
+    let list_memberships = sqlx::query_as!(
+        ListMembershipRecord,
+        r#"
+        SELECT l.slug AS list_slug, l.name AS list_name, m.status, m.created_at
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY m.created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the list memberships of the subscriber.")?;
+    let subscription_tokens = sqlx::query_as!(
+        SubscriptionTokenRecord,
+        r#"
+        SELECT subscription_token, created_at, expires_at, consumed_at, new_email
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        ORDER BY created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the subscription tokens of the subscriber.")?;
+    let pending_deliveries = sqlx::query_as!(
+        PendingDeliveryRecord,
+        r#"
+        SELECT q.newsletter_issue_id, i.title, q.n_retries, q.execute_after
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+        WHERE q.subscriber_id = $1
+        ORDER BY q.execute_after
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the pending deliveries of the subscriber.")?;
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+        SELECT d.delivery_id, d.newsletter_issue_id, i.title, d.delivered_at, d.tracked
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_id = $1
+        ORDER BY d.delivered_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the deliveries to the subscriber.")?;
+    let email_opens = sqlx::query_as!(
+        EmailOpenRecord,
+        r#"
+        SELECT o.delivery_id, o.opened_at, o.user_agent
+        FROM email_opens o
+        JOIN newsletter_deliveries d ON d.delivery_id = o.delivery_id
+        WHERE d.subscriber_id = $1
+        ORDER BY o.opened_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the email opens of the subscriber.")?;
+    let link_clicks = sqlx::query_as!(
+        LinkClickRecord,
+        r#"
+        SELECT c.delivery_id, c.url, c.clicked_at, c.user_agent
+        FROM link_clicks c
+        JOIN newsletter_deliveries d ON d.delivery_id = c.delivery_id
+        WHERE d.subscriber_id = $1
+        ORDER BY c.clicked_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the link clicks of the subscriber.")?;
+    let email_events = sqlx::query_as!(
+        EmailEventRecord,
+        r#"
+        SELECT event_type, reason, occurred_at
+        FROM email_events
+        WHERE subscriber_id = $1
+        ORDER BY occurred_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch the email events of the subscriber.")?;
+
+    Ok(Some(SubscriberDataExport {
+        exported_at: Utc::now(),
+        subscription,
+        list_memberships,
+        subscription_tokens,
+        pending_deliveries,
+        deliveries,
+        email_opens,
+        link_clicks,
+        email_events,
+    }))
+}
+
+#[tracing::instrument(name = "Erase subscriber", skip(db_pool))]
+pub(crate) async fn erase_subscriber(
+    db_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let email = sqlx::query!(
+        r#"DELETE FROM subscriptions WHERE id = $1 RETURNING email"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to delete the subscriber.")?
+    .map(|row| row.email);
+    let email = match email {
+        Some(email) => email,
+        None => return Ok(false),
+    };
+    sqlx::query!(
+        r#"DELETE FROM rate_limit_buckets WHERE key = $1"#,
+        format!("email:{}", email.to_lowercase())
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete the rate limits of the subscriber.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber.")?;
+
+    Ok(true)
+}
+
+async fn get_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriptionRecord>, anyhow::Error> {
+    let subscription = sqlx::query_as!(
+        SubscriptionRecord,
+        r#"
+        SELECT id, email, name, status, frequency, subscribed_at, last_delivered_at,
+            unsubscribe_token
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(transaction)
+    .await
+    .context("Failed to fetch the subscription.")?;
+
+    Ok(subscription)
+}
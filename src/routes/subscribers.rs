@@ -0,0 +1,425 @@
+use crate::authentication::AuthenticatedUser;
+use crate::domain::{DeliveryFrequency, SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::routes::subscriber_data::erase_subscriber;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::{TryFrom, TryInto};
+use uuid::Uuid;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    SubscribedAt,
+    Email,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct SubscriberFilters {
+    status: Option<String>,
+    subscribed_after: Option<DateTime<Utc>>,
+    subscribed_before: Option<DateTime<Utc>>,
+    email: Option<String>,
+    #[serde(default)]
+    sort: SortField,
+    #[serde(default)]
+    order: SortOrder,
+    limit: Option<i64>,
+    after: Option<String>,
+}
+
+#[derive(serde::Serialize, sqlx::FromRow)]
+struct SubscriberSummary {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    frequency: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberPage {
+    subscribers: Vec<SubscriberSummary>,
+    next_cursor: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Cursor {
+    subscribed_at: DateTime<Utc>,
+    email: String,
+    id: Uuid,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        base64::encode_config(
+            serde_json::to_vec(self).expect("A cursor is always serializable."),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    fn decode(s: &str) -> Result<Self, String> {
+        base64::decode_config(s, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| format!("{} is not a valid cursor.", s))
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberDetails {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    frequency: String,
+    subscribed_at: DateTime<Utc>,
+    last_delivered_at: Option<DateTime<Utc>>,
+    lists: Vec<Membership>,
+}
+
+#[derive(serde::Serialize)]
+struct Membership {
+    list_slug: String,
+    status: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberUpdateData {
+    name: Option<String>,
+    email: Option<String>,
+    status: Option<String>,
+    frequency: Option<String>,
+}
+
+struct SubscriberUpdate {
+    name: Option<SubscriberName>,
+    email: Option<SubscriberEmail>,
+    status: Option<SubscriptionStatus>,
+    frequency: Option<DeliveryFrequency>,
+}
+
+impl TryFrom<SubscriberUpdateData> for SubscriberUpdate {
+    type Error = String;
+
+    fn try_from(value: SubscriberUpdateData) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.name.map(SubscriberName::parse).transpose()?,
+            email: value.email.map(SubscriberEmail::parse).transpose()?,
+            status: value
+                .status
+                .as_deref()
+                .map(SubscriptionStatus::parse)
+                .transpose()?,
+            frequency: value
+                .frequency
+                .as_deref()
+                .map(DeliveryFrequency::parse)
+                .transpose()?,
+        })
+    }
+}
+
+#[tracing::instrument(
+    name = "Listing subscribers",
+    skip(db_pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn list_subscribers(
+    user: AuthenticatedUser,
+    filters: web::Query<SubscriberFilters>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let filters = filters.0;
+    let status = filters
+        .status
+        .as_deref()
+        .map(SubscriptionStatus::parse)
+        .transpose()
+        .map_err(ApiError::ValidationError)?;
+    let cursor = filters
+        .after
+        .as_deref()
+        .map(Cursor::decode)
+        .transpose()
+        .map_err(ApiError::ValidationError)?;
+    let limit = filters
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let email_pattern = filters
+        .email
+        .as_deref()
+        .map(|email| format!("%{}%", escape_like_pattern(email)));
+
+    // The sort column and direction cannot be bound as parameters, so this query is built at
+    // runtime - from the two enums only, never from the request itself.
+    let (column, direction, comparison) = match (filters.sort, filters.order) {
+        (SortField::SubscribedAt, SortOrder::Asc) => ("subscribed_at", "ASC", ">"),
+        (SortField::SubscribedAt, SortOrder::Desc) => ("subscribed_at", "DESC", "<"),
+        (SortField::Email, SortOrder::Asc) => ("email", "ASC", ">"),
+        (SortField::Email, SortOrder::Desc) => ("email", "DESC", "<"),
+    };
+    let query = format!(
+        r#"
+        SELECT id, email, name, status, frequency, subscribed_at FROM subscriptions
+        WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::timestamptz IS NULL OR subscribed_at >= $2)
+            AND ($3::timestamptz IS NULL OR subscribed_at < $3)
+            AND ($4::text IS NULL OR email ILIKE $4)
+            AND ($5::uuid IS NULL OR ({column}, id) {comparison} ($6, $5))
+        ORDER BY {column} {direction}, id {direction}
+        LIMIT $7
+        "#,
+        column = column,
+        comparison = comparison,
+        direction = direction
+    );
+    let query = sqlx::query_as::<_, SubscriberSummary>(&query)
+        .bind(status.map(|status| status.as_str()))
+        .bind(filters.subscribed_after)
+        .bind(filters.subscribed_before)
+        .bind(email_pattern)
+        .bind(cursor.as_ref().map(|cursor| cursor.id));
+    let query = match filters.sort {
+        SortField::SubscribedAt => query.bind(cursor.map(|cursor| cursor.subscribed_at)),
+        SortField::Email => query.bind(cursor.map(|cursor| cursor.email)),
+    };
+    // One extra row tells whether there is a next page.
+    let mut subscribers = query
+        .bind(limit + 1)
+        .fetch_all(db_pool.get_ref())
+        .await
+        .context("Failed to fetch the subscribers.")?;
+
+    let next_cursor = if subscribers.len() as i64 > limit {
+        subscribers.truncate(limit as usize);
+        subscribers.last().map(|last| {
+            Cursor {
+                subscribed_at: last.subscribed_at,
+                email: last.email.clone(),
+                id: last.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(SubscriberPage {
+        subscribers,
+        next_cursor,
+    }))
+}
+
+#[tracing::instrument(
+    name = "Getting a subscriber",
+    skip(db_pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn get_subscriber(
+    user: AuthenticatedUser,
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    match get_subscriber_details(&db_pool, *subscriber_id).await? {
+        Some(subscriber) => Ok(HttpResponse::Ok().json(subscriber)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(
+    name = "Updating a subscriber",
+    skip(db_pool, user, body),
+    fields(user_id = %user.user_id)
+)]
+pub async fn update_subscriber(
+    user: AuthenticatedUser,
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<SubscriberUpdateData>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = *subscriber_id;
+    let update: SubscriberUpdate = body.0.try_into().map_err(ApiError::ValidationError)?;
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+
+    if let Some(email) = &update.email {
+        if email_is_taken_by_another(&mut transaction, email, subscriber_id).await? {
+            return Err(ApiError::ValidationError(format!(
+                "{} is used by another subscription already.",
+                email
+            )));
+        }
+    }
+    let updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET
+            name = COALESCE($2, name),
+            email = COALESCE($3, email),
+            status = COALESCE($4, status),
+            frequency = COALESCE($5, frequency)
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        update.name.as_ref().map(|name| name.as_ref()),
+        update.email.as_ref().map(|email| email.as_ref()),
+        update.status.map(|status| status.as_str()),
+        update.frequency.map(|frequency| frequency.as_str())
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update the subscriber.")?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    if let Some(status) = update.status {
+        update_memberships_for_status(&mut transaction, subscriber_id, status)
+            .await
+            .context("Failed to update the list memberships of the subscriber.")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber.")?;
+
+    match get_subscriber_details(&db_pool, subscriber_id).await? {
+        Some(subscriber) => Ok(HttpResponse::Ok().json(subscriber)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[tracing::instrument(
+    name = "Deleting a subscriber",
+    skip(db_pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn delete_subscriber(
+    user: AuthenticatedUser,
+    subscriber_id: web::Path<Uuid>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if erase_subscriber(&db_pool, *subscriber_id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+fn escape_like_pattern(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[tracing::instrument(name = "Get subscriber details", skip(db_pool))]
+async fn get_subscriber_details(
+    db_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberDetails>, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT id, email, name, status, frequency, subscribed_at, last_delivered_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to fetch the subscriber.")?;
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        None => return Ok(None),
+    };
+    let lists = sqlx::query_as!(
+        Membership,
+        r#"
+        SELECT l.slug AS list_slug, m.status
+        FROM list_memberships m
+        JOIN lists l ON l.list_id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(db_pool)
+    .await
+    .context("Failed to fetch the list memberships of the subscriber.")?;
+
+    Ok(Some(SubscriberDetails {
+        id: subscriber.id,
+        email: subscriber.email,
+        name: subscriber.name,
+        status: subscriber.status,
+        frequency: subscriber.frequency,
+        subscribed_at: subscriber.subscribed_at,
+        last_delivered_at: subscriber.last_delivered_at,
+        lists,
+    }))
+}
+
+#[tracing::instrument(name = "Check if an email address is taken", skip(transaction))]
+async fn email_is_taken_by_another(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let taken = sqlx::query!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM subscriptions WHERE email = $1 AND id <> $2) AS "taken!"
+        "#,
+        email.as_ref(),
+        subscriber_id
+    )
+    .fetch_one(transaction)
+    .await
+    .context("Failed to check whether the email address is taken.")?
+    .taken;
+
+    Ok(taken)
+}
+
+#[tracing::instrument(name = "Update list memberships for a status", skip(transaction))]
+async fn update_memberships_for_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+) -> Result<(), sqlx::Error> {
+    let (from, to) = match status {
+        SubscriptionStatus::Confirmed => (vec!["PENDING"], "CONFIRMED"),
+        SubscriptionStatus::Unsubscribed => (vec!["PENDING", "CONFIRMED"], "UNSUBSCRIBED"),
+        SubscriptionStatus::Pending | SubscriptionStatus::Suppressed => return Ok(()),
+    };
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = $3
+        WHERE subscriber_id = $1 AND status = ANY($2)
+        "#,
+        subscriber_id,
+        &from as &[&str],
+        to
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
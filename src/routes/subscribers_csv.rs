@@ -0,0 +1,378 @@
+use crate::authentication::AuthenticatedUser;
+use crate::configuration::SubscriptionSettings;
+use crate::confirmation_email_worker::enqueue_confirmation_email;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::routes::lists::resolve_list_slugs;
+use crate::routes::subscriptions::{generate_subscription_token, store_token};
+use crate::routes::ApiError;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::{Stream, TryStreamExt};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+pub const IMPORT_SIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+const EXPORT_HEADER: [&str; 5] = ["email", "name", "status", "frequency", "subscribed_at"];
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    Confirmed,
+    #[default]
+    DoubleOptIn,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ImportParameters {
+    #[serde(default)]
+    mode: ImportMode,
+    lists: Option<String>,
+}
+
+impl ImportParameters {
+    fn list_slugs(&self) -> Vec<String> {
+        self.lists
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|slug| !slug.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ImportRow {
+    email: String,
+    name: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default, alias = "subscribed_at")]
+    consent_date: Option<String>,
+}
+
+struct ImportedSubscriber {
+    subscriber: NewSubscriber,
+    status: SubscriptionStatus,
+    subscribed_at: DateTime<Utc>,
+}
+
+impl ImportedSubscriber {
+    fn parse(row: ImportRow, mode: ImportMode) -> Result<Self, String> {
+        let subscriber = NewSubscriber {
+            email: SubscriberEmail::parse(row.email)?,
+            name: SubscriberName::parse(row.name)?,
+        };
+        let status = match non_empty(row.status) {
+            Some(status) => SubscriptionStatus::parse(&status.to_uppercase())?,
+            None => SubscriptionStatus::Confirmed,
+        };
+        // Someone who unsubscribed from - or bounced at - the previous provider must not hear from
+        // us again, every other row goes through the opt-in when asked to.
+        let status = match (mode, status) {
+            (
+                ImportMode::DoubleOptIn,
+                SubscriptionStatus::Unsubscribed | SubscriptionStatus::Suppressed,
+            ) => status,
+            (ImportMode::DoubleOptIn, _) => SubscriptionStatus::Pending,
+            (ImportMode::Confirmed, _) => status,
+        };
+        let subscribed_at = match non_empty(row.consent_date) {
+            Some(date) => parse_consent_date(&date)?,
+            None => Utc::now(),
+        };
+        Ok(Self {
+            subscriber,
+            status,
+            subscribed_at,
+        })
+    }
+}
+
+#[derive(serde::Serialize, Default)]
+struct ImportReport {
+    imported: usize,
+    confirmation_emails_queued: usize,
+    errors: Vec<RowError>,
+}
+
+#[derive(serde::Serialize)]
+struct RowError {
+    line: u64,
+    email: Option<String>,
+    error: String,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedSubscriber {
+    email: String,
+    name: String,
+    status: String,
+    frequency: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(
+    name = "Importing subscribers",
+    skip(body, db_pool, settings, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn import_subscribers(
+    user: AuthenticatedUser,
+    parameters: web::Query<ImportParameters>,
+    body: Bytes,
+    db_pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, ApiError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_ref());
+    let headers = reader
+        .headers()
+        .map_err(|e| ApiError::ValidationError(format!("The CSV header is invalid: {}", e)))?
+        .clone();
+    for required in ["email", "name"] {
+        if !headers.iter().any(|header| header == required) {
+            return Err(ApiError::ValidationError(format!(
+                "The CSV has no '{}' column.",
+                required
+            )));
+        }
+    }
+
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let list_ids = resolve_list_slugs(&mut transaction, &parameters.list_slugs()).await?;
+    let mut report = ImportReport::default();
+    for record in reader.records() {
+        let (line, row) = match record {
+            Ok(record) => (
+                record.position().map_or(0, |position| position.line()),
+                record.deserialize::<ImportRow>(Some(&headers)),
+            ),
+            Err(e) => {
+                report.errors.push(RowError {
+                    line: e.position().map_or(0, |position| position.line()),
+                    email: None,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                report.errors.push(RowError {
+                    line,
+                    email: None,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let email = row.email.clone();
+        let imported = match ImportedSubscriber::parse(row, parameters.mode) {
+            Ok(imported) => imported,
+            Err(error) => {
+                report.errors.push(RowError {
+                    line,
+                    email: Some(email),
+                    error,
+                });
+                continue;
+            }
+        };
+        let subscriber_id = insert_imported_subscriber(&mut transaction, &imported, &list_ids)
+            .await
+            .context("Failed to store an imported subscriber.")?;
+        let subscriber_id = match subscriber_id {
+            Some(subscriber_id) => subscriber_id,
+            None => {
+                report.errors.push(RowError {
+                    line,
+                    email: Some(email.clone()),
+                    error: format!("{} is subscribed already.", email),
+                });
+                continue;
+            }
+        };
+        report.imported += 1;
+        if imported.status == SubscriptionStatus::Pending {
+            let subscription_token = generate_subscription_token();
+            store_token(
+                &mut transaction,
+                &subscription_token,
+                &subscriber_id,
+                Utc::now() + settings.confirmation_token_ttl(),
+            )
+            .await
+            .context("Failed to store subscription token.")?;
+            // Admins import on purpose, the confirmation email rate limit is meant for the public
+            // form.
+            enqueue_confirmation_email(&mut transaction, &subscription_token, &subscriber_id)
+                .await
+                .context("Failed to enqueue a confirmation email.")?;
+            report.confirmation_emails_queued += 1;
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers.")?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[tracing::instrument(
+    name = "Exporting subscribers",
+    skip(db_pool, user),
+    fields(user_id = %user.user_id)
+)]
+pub async fn export_subscribers(
+    user: AuthenticatedUser,
+    db_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscribers.csv".into())],
+        })
+        .streaming(exported_subscribers(db_pool.get_ref().clone()))
+}
+
+fn exported_subscribers(db_pool: PgPool) -> impl Stream<Item = Result<Bytes, sqlx::Error>> {
+    async_stream::try_stream! {
+        yield csv_line(EXPORT_HEADER);
+        let mut subscribers = sqlx::query_as!(
+            ExportedSubscriber,
+            r#"
+            SELECT email, name, status, frequency, subscribed_at
+            FROM subscriptions
+            ORDER BY subscribed_at, id
+            "#
+        )
+        .fetch(&db_pool);
+        while let Some(subscriber) = subscribers.try_next().await? {
+            yield csv_line(subscriber);
+        }
+    }
+}
+
+fn csv_line(record: impl serde::Serialize) -> Bytes {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer
+        .serialize(record)
+        .expect("A subscriber is always serializable to CSV.");
+    Bytes::from(writer.into_inner().expect("Writing to memory cannot fail."))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+fn parse_consent_date(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+        .map_err(|_| format!("{} is not a valid consent date.", s))
+}
+
+#[tracing::instrument(
+    name = "Saving an imported subscriber",
+    skip(transaction, imported),
+    fields(subscriber_email = %imported.subscriber.email)
+)]
+async fn insert_imported_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    imported: &ImportedSubscriber,
+    list_ids: &[Uuid],
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let subscriber_id = Uuid::new_v4();
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions(id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        subscriber_id,
+        imported.subscriber.email.as_ref(),
+        imported.subscriber.name.as_ref(),
+        imported.subscribed_at,
+        imported.status.as_str(),
+        generate_subscription_token()
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(None);
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        SELECT list_id, $2, $3 FROM UNNEST($1::uuid[]) AS list_id
+        "#,
+        list_ids,
+        subscriber_id,
+        imported.status.as_str()
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(Some(subscriber_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_consent_date, ImportMode, ImportRow, ImportedSubscriber};
+    use crate::domain::SubscriptionStatus;
+    use claim::{assert_err, assert_ok};
+
+    fn row(status: Option<&str>) -> ImportRow {
+        ImportRow {
+            email: "ursula_le_guin@gmail.com".into(),
+            name: "le guin".into(),
+            status: status.map(String::from),
+            consent_date: None,
+        }
+    }
+
+    #[test]
+    fn double_opt_in_keeps_only_unsubscribed_and_suppressed_rows_as_they_are() {
+        for (status, expected) in [
+            (None, SubscriptionStatus::Pending),
+            (Some("confirmed"), SubscriptionStatus::Pending),
+            (Some("UNSUBSCRIBED"), SubscriptionStatus::Unsubscribed),
+            (Some("SUPPRESSED"), SubscriptionStatus::Suppressed),
+        ] {
+            let imported = ImportedSubscriber::parse(row(status), ImportMode::DoubleOptIn).unwrap();
+            assert_eq!(imported.status, expected);
+        }
+    }
+
+    #[test]
+    fn confirmed_imports_default_to_confirmed() {
+        let imported = ImportedSubscriber::parse(row(None), ImportMode::Confirmed).unwrap();
+        assert_eq!(imported.status, SubscriptionStatus::Confirmed);
+        assert!(ImportedSubscriber::parse(row(Some("ACTIVE")), ImportMode::Confirmed).is_err());
+    }
+
+    #[test]
+    fn consent_dates_are_rfc_3339_timestamps_or_plain_dates() {
+        assert_ok!(parse_consent_date("2022-06-18T09:13:42.123456Z"));
+        assert_ok!(parse_consent_date("2022-06-18"));
+        assert_err!(parse_consent_date("18/06/2022"));
+    }
+}
@@ -1,10 +1,13 @@
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::configuration::SubscriptionSettings;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus};
 use crate::email_client::EmailClient;
+use crate::rate_limit::Decision;
 use crate::routes::errors::{ApiError, StoreTokenError};
-use crate::startup::ApplicationBaseUrl;
+use crate::routes::lists::resolve_list_slugs;
+use crate::startup::{ApplicationBaseUrl, ConfirmationEmailRateLimiter};
 use actix_web::{web, HttpResponse};
 use anyhow::Context;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use sqlx::{PgPool, Postgres, Transaction};
@@ -12,10 +15,30 @@ use std::convert::{TryFrom, TryInto};
 use tera::Tera;
 use uuid::Uuid;
 
+struct ExistingSubscriber {
+    id: Uuid,
+    status: String,
+}
+
 #[derive(serde::Deserialize)]
 pub struct SubscribeFormData {
     email: String,
     name: String,
+    #[serde(default)]
+    lists: Option<String>,
+}
+
+impl SubscribeFormData {
+    fn list_slugs(&self) -> Vec<String> {
+        self.lists
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|slug| !slug.is_empty())
+            .map(String::from)
+            .collect()
+    }
 }
 
 impl TryFrom<SubscribeFormData> for NewSubscriber {
@@ -30,7 +53,7 @@ impl TryFrom<SubscribeFormData> for NewSubscriber {
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, db_pool, email_client, base_url, templates),
+    skip(form, db_pool, email_client, base_url, templates, settings, rate_limiter),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -42,19 +65,60 @@ pub async fn subscribe(
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
     templates: web::Data<Tera>,
+    settings: web::Data<SubscriptionSettings>,
+    rate_limiter: web::Data<ConfirmationEmailRateLimiter>,
 ) -> Result<HttpResponse, ApiError> {
-    let new_subscriber = form.0.try_into().map_err(ApiError::ValidationError)?;
+    let list_slugs = form.list_slugs();
+    let new_subscriber: NewSubscriber = form.0.try_into().map_err(ApiError::ValidationError)?;
+    // Checked before looking the address up, so every address runs into the limit alike.
+    check_confirmation_email_limit(&rate_limiter, &new_subscriber.email).await?;
     let mut transaction = db_pool
         .begin()
         .await
         .context("Failed to acquire database connection from the pool.")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let list_ids = resolve_list_slugs(&mut transaction, &list_slugs).await?;
+    let inserted_id = insert_subscriber(&mut transaction, &new_subscriber)
         .await
         .context("Failed to insert new subscriber to the database.")?;
-    let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, &subscription_token, &subscriber_id)
+    let (subscriber_id, existing_subscriber) = match inserted_id {
+        Some(subscriber_id) => (subscriber_id, None),
+        None => {
+            let subscriber = find_subscriber_by_email(&mut transaction, &new_subscriber.email)
+                .await
+                .context("Failed to look up the subscriber by their email address.")?;
+            if subscriber.status == SubscriptionStatus::Suppressed.as_str() {
+                // Nothing is sent to a suppressed address anymore, not even a confirmation email.
+                // The answer is the same as for any other address.
+                return Ok(HttpResponse::Ok().finish());
+            }
+            (subscriber.id, Some(subscriber))
+        }
+    };
+    let n_pending_lists = request_memberships(&mut transaction, &subscriber_id, &list_ids)
         .await
-        .context("Failed to store subscription token.")?;
+        .context("Failed to store the list memberships of the subscriber.")?;
+    if n_pending_lists == 0 {
+        // Already confirmed on every requested list. Answering exactly like for a new address
+        // keeps the subscription status private.
+        return Ok(HttpResponse::Ok().finish());
+    }
+    if let Some(subscriber) =
+        existing_subscriber.filter(|s| s.status != SubscriptionStatus::Confirmed.as_str())
+    {
+        renew_pending_subscription(&mut transaction, &subscriber.id, &new_subscriber)
+            .await
+            .context("Failed to renew the subscription of an existing subscriber.")?;
+    }
+    let subscription_token = generate_subscription_token();
+    let expires_at = Utc::now() + settings.confirmation_token_ttl();
+    store_token(
+        &mut transaction,
+        &subscription_token,
+        &subscriber_id,
+        expires_at,
+    )
+    .await
+    .context("Failed to store subscription token.")?;
     transaction
         .commit()
         .await
@@ -62,7 +126,7 @@ pub async fn subscribe(
 
     send_confirmation_email(
         &email_client,
-        new_subscriber,
+        &new_subscriber.email,
         subscription_token.as_str(),
         &base_url.0,
         &templates,
@@ -73,7 +137,23 @@ pub async fn subscribe(
     Ok(HttpResponse::Ok().finish())
 }
 
-fn generate_subscription_token() -> String {
+pub(crate) async fn check_confirmation_email_limit(
+    rate_limiter: &ConfirmationEmailRateLimiter,
+    email: &SubscriberEmail,
+) -> Result<(), ApiError> {
+    let key = format!("email:{}", email.as_ref().to_lowercase());
+    let decision = rate_limiter
+        .0
+        .check(&key)
+        .await
+        .context("Failed to check the confirmation email rate limit.")?;
+    match decision {
+        Decision::Allowed => Ok(()),
+        Decision::Limited { retry_after } => Err(ApiError::RateLimited { retry_after }),
+    }
+}
+
+pub(crate) fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -81,6 +161,69 @@ fn generate_subscription_token() -> String {
         .collect()
 }
 
+#[tracing::instrument(name = "Find subscriber by email", skip(transaction))]
+async fn find_subscriber_by_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<ExistingSubscriber, sqlx::Error> {
+    sqlx::query_as!(
+        ExistingSubscriber,
+        r#"
+        SELECT id, status FROM subscriptions WHERE email = $1 FOR UPDATE
+        "#,
+        email.as_ref()
+    )
+    .fetch_one(transaction)
+    .await
+}
+
+#[tracing::instrument(
+    name = "Renewing an existing subscription",
+    skip(transaction, new_subscriber)
+)]
+async fn renew_pending_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: &Uuid,
+    new_subscriber: &NewSubscriber,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET name = $1, status = $3 WHERE id = $2
+        "#,
+        new_subscriber.name.as_ref(),
+        subscriber_id,
+        SubscriptionStatus::Pending.as_str()
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Requesting list memberships", skip(transaction))]
+async fn request_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: &Uuid,
+    list_ids: &[Uuid],
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        SELECT list_id, $2, 'PENDING' FROM UNNEST($1::uuid[]) AS list_id
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE SET status = 'PENDING'
+        WHERE list_memberships.status <> 'CONFIRMED'
+        "#,
+        list_ids,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Concurrent sign-ups for the same address wait for each other here instead of failing on the
+/// unique constraint.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, transaction)
@@ -88,38 +231,42 @@ fn generate_subscription_token() -> String {
 async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let inserted = sqlx::query!(
         r#"
-        INSERT INTO subscriptions(id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO subscriptions(id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id
         "#,
-        subscriber_id,
+        Uuid::new_v4(),
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
         Utc::now(),
-        "PENDING"
+        SubscriptionStatus::Pending.as_str(),
+        generate_subscription_token()
     )
-    .execute(transaction)
+    .fetch_optional(transaction)
     .await?;
 
-    Ok(subscriber_id)
+    Ok(inserted.map(|row| row.id))
 }
 
 #[tracing::instrument(name = "Saving subscription token in the database", skip(transaction))]
-async fn store_token(
+pub(crate) async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscription_token: &str,
     subscriber_id: &Uuid,
+    expires_at: DateTime<Utc>,
 ) -> Result<(), StoreTokenError> {
     sqlx::query!(
         r#"
-        INSERT INTO subscription_tokens(subscription_token, subscriber_id)
-        VALUES ($1, $2)
+        INSERT INTO subscription_tokens(subscription_token, subscriber_id, created_at, expires_at)
+        VALUES ($1, $2, now(), $3)
         "#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        expires_at
     )
     .execute(transaction)
     .await
@@ -129,16 +276,15 @@ async fn store_token(
 }
 
 #[tracing::instrument(
-    name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, templates),
+    name = "Send a confirmation email to a pending subscriber",
+    skip(email_client, subscriber_email, base_url, templates),
     fields(
-        subscriber_email = %new_subscriber.email,
-        subscriber_name = %new_subscriber.name
+        subscriber_email = %subscriber_email
     )
 )]
-async fn send_confirmation_email(
+pub(crate) async fn send_confirmation_email(
     email_client: &EmailClient,
-    new_subscriber: NewSubscriber,
+    subscriber_email: &SubscriberEmail,
     subscription_token: &str,
     base_url: &str,
     templates: &Tera,
@@ -154,12 +300,12 @@ async fn send_confirmation_email(
     let plain_body = templates.render("subscriptions/confirm_subscription_email.txt", &context)?;
 
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
+        .send_email(subscriber_email, "Welcome!", &html_body, &plain_body)
         .await
         .with_context(|| {
             format!(
                 "Sending confirmation email failed for email address: {}.",
-                new_subscriber.email.as_ref()
+                subscriber_email.as_ref()
             )
         })
 }
@@ -1,6 +1,19 @@
-use crate::domain::SubscriptionToken;
+use crate::configuration::{BrandingSettings, SubscriptionSettings};
+use crate::domain::{SubscriberEmail, SubscriptionStatus, SubscriptionToken};
+use crate::email_client::EmailClient;
+use crate::routes::errors::is_unique_violation;
+use crate::routes::utils::{render_html, render_html_with_status, see_other};
+use crate::routes::{
+    check_confirmation_email_limit, generate_subscription_token, send_confirmation_email,
+    store_token, ApiError,
+};
+use crate::startup::{ApplicationBaseUrl, ConfirmationEmailRateLimiter};
+use actix_web::http::StatusCode;
 use actix_web::{web, HttpResponse};
-use sqlx::PgPool;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use tera::Tera;
 use uuid::Uuid;
 
 #[derive(serde::Deserialize)]
@@ -8,87 +21,459 @@ pub struct Parameters {
     subscription_token: String,
 }
 
+struct StoredToken {
+    subscriber_id: Uuid,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+    new_email: Option<String>,
+}
+
+struct PendingSubscriber {
+    id: Uuid,
+    email: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConfirmationOutcome {
+    Confirmed,
+    EmailChanged,
+    EmailTaken,
+    AlreadyConfirmed,
+    Unavailable,
+    Invalid,
+    Expired,
+}
+
+impl ConfirmationOutcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Confirmed => "confirmed",
+            Self::EmailChanged => "email_changed",
+            Self::EmailTaken => "email_taken",
+            Self::AlreadyConfirmed => "already_confirmed",
+            Self::Unavailable => "unavailable",
+            Self::Invalid => "invalid",
+            Self::Expired => "expired",
+        }
+    }
+
+    fn template(&self) -> &'static str {
+        match self {
+            Self::Confirmed | Self::EmailChanged => "subscriptions/confirmation_succeeded.html",
+            Self::EmailTaken => "subscriptions/email_taken.html",
+            Self::AlreadyConfirmed => "subscriptions/already_confirmed.html",
+            Self::Unavailable => "subscriptions/confirmation_unavailable.html",
+            Self::Invalid => "subscriptions/confirmation_invalid.html",
+            Self::Expired => "subscriptions/confirmation_expired.html",
+        }
+    }
+
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Confirmed | Self::EmailChanged | Self::AlreadyConfirmed | Self::Unavailable => {
+                StatusCode::OK
+            }
+            Self::EmailTaken => StatusCode::CONFLICT,
+            Self::Invalid => StatusCode::UNAUTHORIZED,
+            Self::Expired => StatusCode::GONE,
+        }
+    }
+}
+
+/// Mail scanners following every link in an email must not confirm anything.
 #[tracing::instrument(
-    name = "Confirm a pending subscriber",
-    skip(db_pool, _parameters),
+    name = "Render confirmation form",
+    skip(db_pool, parameters, templates, settings, branding),
     fields(
-        subscription_token = %_parameters.subscription_token
+        subscription_token = %parameters.subscription_token
     )
 )]
-pub async fn confirm(
+pub async fn confirm_form(
     db_pool: web::Data<PgPool>,
-    _parameters: web::Query<Parameters>,
-) -> HttpResponse {
-    let token = match SubscriptionToken::parse(_parameters.subscription_token.clone()) {
+    parameters: web::Query<Parameters>,
+    templates: web::Data<Tera>,
+    settings: web::Data<SubscriptionSettings>,
+    branding: web::Data<BrandingSettings>,
+) -> Result<HttpResponse, ApiError> {
+    let subscription_token = parameters.0.subscription_token;
+    if settings.one_click_confirmation {
+        return confirm_and_respond(
+            &db_pool,
+            subscription_token,
+            &templates,
+            &settings,
+            &branding,
+        )
+        .await;
+    }
+
+    let token = match SubscriptionToken::parse(subscription_token.clone()) {
         Ok(token) => token,
-        Err(error) => {
-            tracing::error!("Received subscription token is not valid: {}", error);
-            return HttpResponse::BadRequest().finish();
+        Err(_) => {
+            return respond(
+                ConfirmationOutcome::Invalid,
+                &subscription_token,
+                &templates,
+                &settings,
+                &branding,
+            )
         }
     };
-
-    let id = match find_subscriber_id(&db_pool, &token).await {
-        Ok(subscriber_id) => subscriber_id,
-        Err(error) => {
-            tracing::error!(
-                "Finding subscriber id for subscription token failed: {:?}",
-                error
-            );
-            return HttpResponse::InternalServerError().finish();
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let stored_token = find_token(&mut transaction, &token).await?;
+    let stored_token = match settle_token(stored_token) {
+        Ok(stored_token) => stored_token,
+        Err(outcome) => {
+            return respond(
+                outcome,
+                &subscription_token,
+                &templates,
+                &settings,
+                &branding,
+            )
         }
     };
 
-    match id {
-        None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => match mark_subscriber_as_confirmed(&db_pool, &subscriber_id).await {
-            Ok(()) => HttpResponse::Ok().finish(),
-            Err(error) => {
-                tracing::error!("Marking subscriber status as confirmed failed: {:?}", error);
-                HttpResponse::InternalServerError().finish()
+    let mut context = tera::Context::new();
+    context.insert("branding", branding.get_ref());
+    context.insert("subscription_token", &subscription_token);
+    context.insert("email_change", &stored_token.new_email.is_some());
+    render_html(&templates, "subscriptions/confirm.html", &context)
+}
+
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(db_pool, form, templates, settings, branding),
+    fields(
+        subscription_token = %form.subscription_token
+    )
+)]
+pub async fn confirm(
+    db_pool: web::Data<PgPool>,
+    form: web::Form<Parameters>,
+    templates: web::Data<Tera>,
+    settings: web::Data<SubscriptionSettings>,
+    branding: web::Data<BrandingSettings>,
+) -> Result<HttpResponse, ApiError> {
+    confirm_and_respond(
+        &db_pool,
+        form.0.subscription_token,
+        &templates,
+        &settings,
+        &branding,
+    )
+    .await
+}
+
+async fn confirm_and_respond(
+    db_pool: &PgPool,
+    subscription_token: String,
+    templates: &Tera,
+    settings: &SubscriptionSettings,
+    branding: &BrandingSettings,
+) -> Result<HttpResponse, ApiError> {
+    let outcome = match SubscriptionToken::parse(subscription_token.clone()) {
+        Ok(token) => confirm_token(db_pool, &token).await?,
+        Err(_) => ConfirmationOutcome::Invalid,
+    };
+    respond(outcome, &subscription_token, templates, settings, branding)
+}
+
+fn respond(
+    outcome: ConfirmationOutcome,
+    subscription_token: &str,
+    templates: &Tera,
+    settings: &SubscriptionSettings,
+    branding: &BrandingSettings,
+) -> Result<HttpResponse, ApiError> {
+    match &settings.confirmation_redirect_url {
+        Some(redirect_url) => {
+            let mut location = reqwest::Url::parse(redirect_url)
+                .context("The confirmation redirect URL is invalid.")?;
+            location
+                .query_pairs_mut()
+                .append_pair("status", outcome.as_str());
+            // The external page needs the token to offer resending the confirmation email.
+            if outcome == ConfirmationOutcome::Expired {
+                location
+                    .query_pairs_mut()
+                    .append_pair("subscription_token", subscription_token);
             }
-        },
+            Ok(see_other(location.as_str()))
+        }
+        None => {
+            let mut context = tera::Context::new();
+            context.insert("branding", branding);
+            context.insert(
+                "email_changed",
+                &(outcome == ConfirmationOutcome::EmailChanged),
+            );
+            context.insert("subscription_token", subscription_token);
+            render_html_with_status(
+                templates,
+                outcome.template(),
+                &context,
+                outcome.status_code(),
+            )
+        }
     }
 }
 
-#[tracing::instrument(name = "Find subscriber id from subscription token", skip(db_pool))]
-async fn find_subscriber_id(
+fn settle_token(stored_token: Option<StoredToken>) -> Result<StoredToken, ConfirmationOutcome> {
+    let stored_token = stored_token.ok_or(ConfirmationOutcome::Invalid)?;
+    // Tokens are single-use - following the link again only tells the subscriber they are done.
+    if stored_token.consumed_at.is_some() {
+        return Err(ConfirmationOutcome::AlreadyConfirmed);
+    }
+    if stored_token.expires_at <= Utc::now() {
+        return Err(ConfirmationOutcome::Expired);
+    }
+    Ok(stored_token)
+}
+
+async fn confirm_token(
     db_pool: &PgPool,
+    token: &SubscriptionToken,
+) -> Result<ConfirmationOutcome, ApiError> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let stored_token = find_token(&mut transaction, token).await?;
+    let stored_token = match settle_token(stored_token) {
+        Ok(stored_token) => stored_token,
+        Err(outcome) => return Ok(outcome),
+    };
+
+    let outcome = match &stored_token.new_email {
+        Some(new_email) => {
+            if !change_email(&mut transaction, &stored_token.subscriber_id, new_email).await? {
+                return Ok(ConfirmationOutcome::EmailTaken);
+            }
+            ConfirmationOutcome::EmailChanged
+        }
+        None => {
+            let confirmed =
+                mark_subscriber_as_confirmed(&mut transaction, &stored_token.subscriber_id)
+                    .await
+                    .context("Failed to mark the subscriber as confirmed.")?;
+            if confirmed {
+                confirm_pending_memberships(&mut transaction, &stored_token.subscriber_id)
+                    .await
+                    .context("Failed to confirm the list memberships of the subscriber.")?;
+                ConfirmationOutcome::Confirmed
+            } else {
+                ConfirmationOutcome::Unavailable
+            }
+        }
+    };
+    consume_token(&mut transaction, token)
+        .await
+        .context("Failed to mark the subscription token as consumed.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber.")?;
+
+    Ok(outcome)
+}
+
+#[tracing::instrument(
+    name = "Resend a confirmation email",
+    skip(form, db_pool, email_client, base_url, templates, settings, rate_limiter),
+    fields(
+        subscription_token = %form.subscription_token
+    )
+)]
+pub async fn resend_confirmation(
+    form: web::Form<Parameters>,
+    db_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    templates: web::Data<Tera>,
+    settings: web::Data<SubscriptionSettings>,
+    rate_limiter: web::Data<ConfirmationEmailRateLimiter>,
+) -> Result<HttpResponse, ApiError> {
+    let token =
+        SubscriptionToken::parse(form.0.subscription_token).map_err(ApiError::ValidationError)?;
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let subscriber = match find_pending_subscriber(&mut transaction, &token).await? {
+        Some(subscriber) => subscriber,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+    let subscriber_email = SubscriberEmail::parse(subscriber.email)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("The stored email address of a pending subscriber is invalid.")?;
+    check_confirmation_email_limit(&rate_limiter, &subscriber_email).await?;
+
+    let subscription_token = generate_subscription_token();
+    let expires_at = Utc::now() + settings.confirmation_token_ttl();
+    store_token(
+        &mut transaction,
+        &subscription_token,
+        &subscriber.id,
+        expires_at,
+    )
+    .await
+    .context("Failed to store subscription token.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new subscription token.")?;
+
+    send_confirmation_email(
+        &email_client,
+        &subscriber_email,
+        subscription_token.as_str(),
+        &base_url.0,
+        &templates,
+    )
+    .await
+    .context("Failed to send a confirmation email.")?;
+
+    let mut context = tera::Context::new();
+    context.insert("email", subscriber_email.as_ref());
+    render_html(
+        &templates,
+        "subscriptions/confirmation_resent.html",
+        &context,
+    )
+}
+
+#[tracing::instrument(name = "Find subscription token", skip(transaction))]
+async fn find_token(
+    transaction: &mut Transaction<'_, Postgres>,
     subscription_token: &SubscriptionToken,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
+) -> Result<Option<StoredToken>, anyhow::Error> {
+    let stored_token = sqlx::query_as!(
+        StoredToken,
+        r#"
+        SELECT subscriber_id, expires_at, consumed_at, new_email FROM subscription_tokens
+        WHERE subscription_token = $1
+        FOR UPDATE
+        "#,
+        subscription_token.as_ref(),
+    )
+    .fetch_optional(transaction)
+    .await
+    .context("Failed to find the subscription token.")?;
+
+    Ok(stored_token)
+}
+
+#[tracing::instrument(
+    name = "Find pending subscriber from subscription token",
+    skip(transaction)
+)]
+async fn find_pending_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &SubscriptionToken,
+) -> Result<Option<PendingSubscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        PendingSubscriber,
         r#"
-        SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1
+        SELECT s.id, s.email FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE t.subscription_token = $1 AND t.new_email IS NULL AND s.status <> $2
+        AND EXISTS (
+            SELECT 1 FROM list_memberships m
+            WHERE m.subscriber_id = s.id AND m.status = 'PENDING'
+        )
         "#,
         subscription_token.as_ref(),
+        SubscriptionStatus::Suppressed.as_str()
     )
-    .fetch_optional(db_pool)
+    .fetch_optional(transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
+    .context("Failed to find the subscriber by their subscription token.")?;
 
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(subscriber)
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(db_pool))]
+/// Suppressed subscribers stay so, even with a link sent before they bounced.
+#[tracing::instrument(name = "Mark subscriber as confirmed", skip(transaction))]
 async fn mark_subscriber_as_confirmed(
-    db_pool: &PgPool,
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: &Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status=$1 WHERE id=$2 AND status <> $3
+        "#,
+        SubscriptionStatus::Confirmed.as_str(),
+        subscriber_id,
+        SubscriptionStatus::Suppressed.as_str()
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(name = "Change the email of a subscriber", skip(transaction))]
+async fn change_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: &Uuid,
+    new_email: &str,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET email = $1
+        WHERE id = $2 AND NOT EXISTS (SELECT 1 FROM subscriptions WHERE email = $1)
+        "#,
+        new_email,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await;
+
+    match result {
+        Ok(result) => Ok(result.rows_affected() == 1),
+        // A concurrent confirmation took the address in between the check and the update.
+        Err(e) if is_unique_violation(&e) => Ok(false),
+        Err(e) => {
+            Err(anyhow::Error::new(e).context("Failed to change the email of the subscriber."))
+        }
+    }
+}
+
+#[tracing::instrument(name = "Confirm pending list memberships", skip(transaction))]
+async fn confirm_pending_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: &Uuid,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        UPDATE subscriptions SET status=$1 WHERE id=$2
+        UPDATE list_memberships SET status = 'CONFIRMED'
+        WHERE subscriber_id = $1 AND status = 'PENDING'
         "#,
-        "CONFIRMED",
         subscriber_id
     )
-    .execute(db_pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Consume subscription token", skip(transaction))]
+async fn consume_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &SubscriptionToken,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscription_tokens SET consumed_at = now() WHERE subscription_token = $1
+        "#,
+        subscription_token.as_ref()
+    )
+    .execute(transaction)
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,179 @@
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::magic_link::MagicLinkError;
+use crate::routes::subscriber_data::{
+    collect_subscriber_data, erase_subscriber, find_subscriber_id,
+};
+use crate::routes::utils::{render_html, render_html_with_status};
+use crate::routes::{check_confirmation_email_limit, ApiError};
+use crate::startup::ConfirmationEmailRateLimiter;
+use crate::subscriber_links::SubscriberLinks;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use tera::Tera;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataLinkParameters {
+    token: String,
+}
+
+#[tracing::instrument(name = "Render data request form", skip(templates))]
+pub async fn data_request_form(templates: web::Data<Tera>) -> Result<HttpResponse, ApiError> {
+    render_html(
+        &templates,
+        "subscriptions/data_request.html",
+        &tera::Context::new(),
+    )
+}
+
+/// The answer is the same whether or not the address is subscribed.
+#[tracing::instrument(
+    name = "Request subscriber data",
+    skip(form, db_pool, email_client, templates, rate_limiter, links)
+)]
+pub async fn request_data(
+    form: web::Form<DataRequestFormData>,
+    db_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    templates: web::Data<Tera>,
+    rate_limiter: web::Data<ConfirmationEmailRateLimiter>,
+    links: web::Data<SubscriberLinks>,
+) -> Result<HttpResponse, ApiError> {
+    let email = SubscriberEmail::parse(form.0.email).map_err(ApiError::ValidationError)?;
+    if let Some(subscriber_id) = find_subscriber_id(&db_pool, &email).await? {
+        match check_confirmation_email_limit(&rate_limiter, &email).await {
+            Ok(()) => {
+                send_data_request_email(&email_client, &email, subscriber_id, &links, &templates)
+                    .await
+                    .context("Failed to send the data request email.")?
+            }
+            // A 429 would give away that the address is subscribed.
+            Err(ApiError::RateLimited { .. }) => {
+                tracing::warn!("Not sending a data request email, the address is rate limited.")
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("email", email.as_ref());
+    render_html(&templates, "subscriptions/data_request_sent.html", &context)
+}
+
+#[tracing::instrument(
+    name = "Export subscriber data",
+    skip(parameters, db_pool, links, templates)
+)]
+pub async fn export_data(
+    parameters: web::Query<DataLinkParameters>,
+    db_pool: web::Data<PgPool>,
+    links: web::Data<SubscriberLinks>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = match links.verify_data_export_token(&parameters.token) {
+        Ok(subscriber_id) => subscriber_id,
+        Err(error) => return reject_link(error, &templates),
+    };
+    let export = match collect_subscriber_data(&db_pool, subscriber_id).await? {
+        Some(export) => export,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscriber-data.json".into())],
+        })
+        .json(export))
+}
+
+#[tracing::instrument(name = "Render data erasure form", skip(parameters, links, templates))]
+pub async fn erase_data_form(
+    parameters: web::Query<DataLinkParameters>,
+    links: web::Data<SubscriberLinks>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    if let Err(error) = links.verify_data_erasure_token(&parameters.token) {
+        return reject_link(error, &templates);
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("token", &parameters.token);
+    render_html(&templates, "subscriptions/data_erasure.html", &context)
+}
+
+#[tracing::instrument(
+    name = "Erase subscriber data",
+    skip(parameters, db_pool, links, templates)
+)]
+pub async fn erase_data(
+    parameters: web::Query<DataLinkParameters>,
+    db_pool: web::Data<PgPool>,
+    links: web::Data<SubscriberLinks>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = match links.verify_data_erasure_token(&parameters.token) {
+        Ok(subscriber_id) => subscriber_id,
+        Err(error) => return reject_link(error, &templates),
+    };
+    // Following the link twice is fine, the data is gone either way.
+    erase_subscriber(&db_pool, subscriber_id).await?;
+
+    render_html(
+        &templates,
+        "subscriptions/data_erased.html",
+        &tera::Context::new(),
+    )
+}
+
+fn reject_link(error: MagicLinkError, templates: &Tera) -> Result<HttpResponse, ApiError> {
+    match error {
+        MagicLinkError::Invalid => Ok(HttpResponse::Unauthorized().finish()),
+        MagicLinkError::Expired => render_html_with_status(
+            templates,
+            "subscriptions/data_link_expired.html",
+            &tera::Context::new(),
+            StatusCode::GONE,
+        ),
+    }
+}
+
+#[tracing::instrument(
+    name = "Send a data request email",
+    skip(email_client, email, links, templates),
+    fields(
+        subscriber_email = %email
+    )
+)]
+async fn send_data_request_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    subscriber_id: Uuid,
+    links: &SubscriberLinks,
+    templates: &Tera,
+) -> Result<(), anyhow::Error> {
+    let mut context = tera::Context::new();
+    context.insert("export_link", &links.data_export(subscriber_id));
+    context.insert("erasure_link", &links.data_erasure(subscriber_id));
+    let html_body = templates.render("subscriptions/data_request_email.html", &context)?;
+    let plain_body = templates.render("subscriptions/data_request_email.txt", &context)?;
+
+    email_client
+        .send_email(email, "Your data", &html_body, &plain_body)
+        .await
+        .with_context(|| {
+            format!(
+                "Sending the data request email failed for email address: {}.",
+                email.as_ref()
+            )
+        })
+}
@@ -0,0 +1,446 @@
+use crate::configuration::SubscriptionSettings;
+use crate::domain::{DeliveryFrequency, SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::email_client::EmailClient;
+use crate::magic_link::MagicLinkError;
+use crate::routes::errors::StoreTokenError;
+use crate::routes::utils::{render_html, render_html_with_status, see_other};
+use crate::routes::{check_confirmation_email_limit, generate_subscription_token, ApiError};
+use crate::session::{FlashMessage, TypedSession};
+use crate::startup::{ApplicationBaseUrl, ConfirmationEmailRateLimiter};
+use crate::subscriber_links::SubscriberLinks;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::convert::TryFrom;
+use tera::Tera;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    token: String,
+}
+
+struct Subscriber {
+    email: String,
+    name: String,
+    status: String,
+    frequency: String,
+    unsubscribe_token: String,
+}
+
+#[derive(serde::Serialize)]
+struct ListChoice {
+    list_id: Uuid,
+    name: String,
+    subscribed: bool,
+}
+
+#[derive(serde::Serialize)]
+struct FrequencyChoice {
+    value: &'static str,
+    label: &'static str,
+}
+
+struct PreferencesFormData {
+    name: SubscriberName,
+    email: SubscriberEmail,
+    frequency: DeliveryFrequency,
+    list_ids: Vec<Uuid>,
+}
+
+impl TryFrom<Vec<(String, String)>> for PreferencesFormData {
+    type Error = String;
+
+    fn try_from(pairs: Vec<(String, String)>) -> Result<Self, Self::Error> {
+        let field = |name: &str| {
+            pairs
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.trim().to_string())
+                .unwrap_or_default()
+        };
+        let list_ids = pairs
+            .iter()
+            .filter(|(key, _)| key == "lists")
+            .map(|(_, value)| {
+                Uuid::parse_str(value).map_err(|_| format!("{} is not a valid list.", value))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            name: SubscriberName::parse(field("name"))?,
+            email: SubscriberEmail::parse(field("email"))?,
+            frequency: DeliveryFrequency::parse(&field("frequency"))?,
+            list_ids,
+        })
+    }
+}
+
+#[tracing::instrument(
+    name = "Render subscriber preferences",
+    skip(parameters, session, db_pool, links, templates)
+)]
+pub async fn preferences_form(
+    parameters: web::Query<PreferencesParameters>,
+    session: TypedSession,
+    db_pool: web::Data<PgPool>,
+    links: web::Data<SubscriberLinks>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = match links.verify_preferences_token(&parameters.token) {
+        Ok(subscriber_id) => subscriber_id,
+        Err(error) => return reject_link(error, &templates),
+    };
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let subscriber = match find_subscriber(&mut transaction, subscriber_id).await? {
+        Some(subscriber) => subscriber,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+    let lists = get_list_choices(&mut transaction, subscriber_id).await?;
+    let flash_messages = session
+        .take_flash_messages()
+        .context("Failed to read flash messages.")?;
+    let frequencies: Vec<_> = DeliveryFrequency::ALL
+        .iter()
+        .map(|frequency| FrequencyChoice {
+            value: frequency.as_str(),
+            label: frequency.label(),
+        })
+        .collect();
+
+    let mut context = tera::Context::new();
+    context.insert("flash_messages", &flash_messages);
+    context.insert("token", &parameters.token);
+    context.insert("name", &subscriber.name);
+    context.insert("email", &subscriber.email);
+    context.insert("frequency", &subscriber.frequency);
+    context.insert("frequencies", &frequencies);
+    context.insert("lists", &lists);
+    context.insert("unsubscribe_token", &subscriber.unsubscribe_token);
+    context.insert("data_export_link", &links.data_export(subscriber_id));
+    context.insert("data_erasure_link", &links.data_erasure(subscriber_id));
+    render_html(&templates, "subscriptions/preferences.html", &context)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "Update subscriber preferences",
+    skip(
+        parameters,
+        form,
+        session,
+        db_pool,
+        email_client,
+        base_url,
+        templates,
+        settings,
+        rate_limiter,
+        links
+    )
+)]
+pub async fn update_preferences(
+    parameters: web::Query<PreferencesParameters>,
+    form: web::Form<Vec<(String, String)>>,
+    session: TypedSession,
+    db_pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    templates: web::Data<Tera>,
+    settings: web::Data<SubscriptionSettings>,
+    rate_limiter: web::Data<ConfirmationEmailRateLimiter>,
+    links: web::Data<SubscriberLinks>,
+) -> Result<HttpResponse, ApiError> {
+    let subscriber_id = match links.verify_preferences_token(&parameters.token) {
+        Ok(subscriber_id) => subscriber_id,
+        Err(error) => return reject_link(error, &templates),
+    };
+    let preferences_page = format!("/subscriptions/preferences?token={}", parameters.token);
+    let preferences = match PreferencesFormData::try_from(form.0) {
+        Ok(preferences) => preferences,
+        Err(error) => {
+            session
+                .add_flash_message(FlashMessage::error(error))
+                .context("Failed to store a flash message.")?;
+            return Ok(see_other(&preferences_page));
+        }
+    };
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let subscriber = match find_subscriber(&mut transaction, subscriber_id).await? {
+        Some(subscriber) => subscriber,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+
+    let email_changed = preferences.email.as_ref() != subscriber.email;
+    if email_changed && email_is_taken(&mut transaction, &preferences.email).await? {
+        session
+            .add_flash_message(FlashMessage::error(
+                "Another subscription uses this email address already.",
+            ))
+            .context("Failed to store a flash message.")?;
+        return Ok(see_other(&preferences_page));
+    }
+
+    update_subscriber(&mut transaction, subscriber_id, &preferences)
+        .await
+        .context("Failed to update the subscriber preferences.")?;
+    // Ticking a list is as good as confirming it for a confirmed subscriber, the link they used
+    // reached their inbox. Anyone else still has to confirm their subscription first.
+    let membership_status = if subscriber.status == SubscriptionStatus::Confirmed.as_str() {
+        "CONFIRMED"
+    } else {
+        "PENDING"
+    };
+    update_memberships(
+        &mut transaction,
+        subscriber_id,
+        &preferences.list_ids,
+        membership_status,
+    )
+    .await
+    .context("Failed to update the list memberships of the subscriber.")?;
+
+    if email_changed {
+        check_confirmation_email_limit(&rate_limiter, &preferences.email).await?;
+        let subscription_token = generate_subscription_token();
+        let expires_at = Utc::now() + settings.confirmation_token_ttl();
+        store_email_change_token(
+            &mut transaction,
+            &subscription_token,
+            &subscriber_id,
+            &preferences.email,
+            expires_at,
+        )
+        .await
+        .context("Failed to store the email change token.")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to update subscriber preferences.")?;
+        send_email_change_confirmation(
+            &email_client,
+            &preferences.email,
+            &subscription_token,
+            &base_url.0,
+            &templates,
+        )
+        .await
+        .context("Failed to send the email change confirmation.")?;
+        session
+            .add_flash_message(FlashMessage::info(format!(
+                "We sent a confirmation link to {}. Your email address changes once you follow it.",
+                preferences.email.as_ref()
+            )))
+            .context("Failed to store a flash message.")?;
+    } else {
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to update subscriber preferences.")?;
+    }
+
+    session
+        .add_flash_message(FlashMessage::info("Your preferences have been saved."))
+        .context("Failed to store a flash message.")?;
+    Ok(see_other(&preferences_page))
+}
+
+fn reject_link(error: MagicLinkError, templates: &Tera) -> Result<HttpResponse, ApiError> {
+    match error {
+        MagicLinkError::Invalid => Ok(HttpResponse::Unauthorized().finish()),
+        MagicLinkError::Expired => render_html_with_status(
+            templates,
+            "subscriptions/preferences_expired.html",
+            &tera::Context::new(),
+            StatusCode::GONE,
+        ),
+    }
+}
+
+#[tracing::instrument(name = "Find subscriber by id", skip(transaction))]
+async fn find_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT email, name, status, frequency, unsubscribe_token FROM subscriptions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(transaction)
+    .await
+    .context("Failed to find the subscriber.")?;
+
+    Ok(subscriber)
+}
+
+#[tracing::instrument(name = "Get list choices of a subscriber", skip(transaction))]
+async fn get_list_choices(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListChoice>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        ListChoice,
+        r#"
+        SELECT l.list_id, l.name,
+            COALESCE(m.status IN ('CONFIRMED', 'PENDING'), false) AS "subscribed!"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.list_id AND m.subscriber_id = $1
+        ORDER BY l.created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(transaction)
+    .await
+    .context("Failed to fetch the mailing lists of the subscriber.")?;
+
+    Ok(lists)
+}
+
+#[tracing::instrument(name = "Check if an email address is taken", skip(transaction))]
+async fn email_is_taken(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<bool, anyhow::Error> {
+    let taken = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM subscriptions WHERE email = $1) AS "taken!""#,
+        email.as_ref()
+    )
+    .fetch_one(transaction)
+    .await
+    .context("Failed to check whether the email address is taken.")?
+    .taken;
+
+    Ok(taken)
+}
+
+#[tracing::instrument(name = "Update subscriber", skip(transaction, preferences))]
+async fn update_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    preferences: &PreferencesFormData,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET name = $1, frequency = $2 WHERE id = $3
+        "#,
+        preferences.name.as_ref(),
+        preferences.frequency.as_str(),
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Update list memberships", skip(transaction))]
+async fn update_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_ids: &[Uuid],
+    status: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status)
+        SELECT list_id, $2, $3 FROM lists WHERE list_id = ANY($1)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE SET status = $3
+        WHERE list_memberships.status <> 'CONFIRMED'
+        "#,
+        list_ids,
+        subscriber_id,
+        status
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'UNSUBSCRIBED'
+        WHERE subscriber_id = $1 AND NOT (list_id = ANY($2))
+        "#,
+        subscriber_id,
+        list_ids
+    )
+    .execute(transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Saving email change token in the database", skip(transaction))]
+async fn store_email_change_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &str,
+    subscriber_id: &Uuid,
+    new_email: &SubscriberEmail,
+    expires_at: DateTime<Utc>,
+) -> Result<(), StoreTokenError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens
+            (subscription_token, subscriber_id, created_at, expires_at, new_email)
+        VALUES ($1, $2, now(), $3, $4)
+        "#,
+        subscription_token,
+        subscriber_id,
+        expires_at,
+        new_email.as_ref()
+    )
+    .execute(transaction)
+    .await
+    .map_err(StoreTokenError)?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Send an email change confirmation",
+    skip(email_client, new_email, base_url, templates),
+    fields(
+        new_email = %new_email
+    )
+)]
+async fn send_email_change_confirmation(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    subscription_token: &str,
+    base_url: &str,
+    templates: &Tera,
+) -> Result<(), anyhow::Error> {
+    let confirmation_link = format!(
+        "{}/subscriptions/confirm?subscription_token={}",
+        base_url, subscription_token
+    );
+
+    let mut context = tera::Context::new();
+    context.insert("confirmation_link", &confirmation_link);
+    let html_body = templates.render("subscriptions/confirm_email_change_email.html", &context)?;
+    let plain_body = templates.render("subscriptions/confirm_email_change_email.txt", &context)?;
+
+    email_client
+        .send_email(
+            new_email,
+            "Confirm your new email address",
+            &html_body,
+            &plain_body,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Sending email change confirmation failed for email address: {}.",
+                new_email.as_ref()
+            )
+        })
+}
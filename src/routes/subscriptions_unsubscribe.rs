@@ -0,0 +1,109 @@
+use crate::domain::{SubscriptionStatus, SubscriptionToken};
+use crate::routes::utils::render_html;
+use crate::routes::ApiError;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use tera::Tera;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    token: String,
+}
+
+struct Subscriber {
+    id: Uuid,
+    email: String,
+}
+
+#[tracing::instrument(name = "Render unsubscribe form", skip(parameters, db_pool, templates))]
+pub async fn unsubscribe_form(
+    parameters: web::Query<UnsubscribeParameters>,
+    db_pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let token = SubscriptionToken::parse(parameters.0.token).map_err(ApiError::ValidationError)?;
+    let subscriber = match find_subscriber(&db_pool, &token).await? {
+        Some(subscriber) => subscriber,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("email", &subscriber.email);
+    context.insert("token", token.as_ref());
+    render_html(&templates, "subscriptions/unsubscribe.html", &context)
+}
+
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(parameters, db_pool, templates)
+)]
+pub async fn unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    db_pool: web::Data<PgPool>,
+    templates: web::Data<Tera>,
+) -> Result<HttpResponse, ApiError> {
+    let token = SubscriptionToken::parse(parameters.0.token).map_err(ApiError::ValidationError)?;
+    let subscriber = match find_subscriber(&db_pool, &token).await? {
+        Some(subscriber) => subscriber,
+        None => return Ok(HttpResponse::Unauthorized().finish()),
+    };
+
+    mark_subscriber_as_unsubscribed(&db_pool, subscriber.id)
+        .await
+        .context("Failed to mark the subscriber as unsubscribed.")?;
+
+    let mut context = tera::Context::new();
+    context.insert("email", &subscriber.email);
+    render_html(&templates, "subscriptions/unsubscribed.html", &context)
+}
+
+#[tracing::instrument(name = "Find subscriber from unsubscribe token", skip(db_pool))]
+async fn find_subscriber(
+    db_pool: &PgPool,
+    token: &SubscriptionToken,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email FROM subscriptions WHERE unsubscribe_token = $1
+        "#,
+        token.as_ref()
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to find the subscriber by their unsubscribe token.")?;
+
+    Ok(subscriber)
+}
+
+/// Suppressed subscribers stay suppressed - the status is what keeps them from signing up again.
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip(db_pool))]
+pub(crate) async fn mark_subscriber_as_unsubscribed(
+    db_pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2 WHERE id = $1 AND status <> $3
+        "#,
+        subscriber_id,
+        SubscriptionStatus::Unsubscribed.as_str(),
+        SubscriptionStatus::Suppressed.as_str()
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'UNSUBSCRIBED' WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use crate::click_tracking::is_web_link;
+use crate::magic_link::MagicLinkError;
+use crate::routes::ApiError;
+use crate::subscriber_links::SubscriberLinks;
+use actix_web::http::header::{CACHE_CONTROL, LOCATION, USER_AGENT};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const TRANSPARENT_GIF: [u8; 42] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x01, 0x44, 0x00, 0x3b,
+];
+
+#[tracing::instrument(name = "Tracking an email open", skip(request, db_pool))]
+pub async fn track_open(
+    request: HttpRequest,
+    delivery_id: web::Path<String>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    if let Ok(delivery_id) = delivery_id.parse::<Uuid>() {
+        record_open(&db_pool, delivery_id, user_agent(&request))
+            .await
+            .context("Failed to record an email open.")?;
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        // Every time the email is opened has to reach us, not a cached copy.
+        .insert_header((
+            CACHE_CONTROL,
+            "no-store, no-cache, must-revalidate, private",
+        ))
+        .body(TRANSPARENT_GIF.as_ref()))
+}
+
+#[tracing::instrument(name = "Tracking a link click", skip(request, token, db_pool, links))]
+pub async fn track_click(
+    request: HttpRequest,
+    token: web::Path<String>,
+    db_pool: web::Data<PgPool>,
+    links: web::Data<SubscriberLinks>,
+) -> Result<HttpResponse, ApiError> {
+    let (delivery_id, url) = match links.verify_click_token(&token) {
+        Ok((delivery_id, url)) if is_web_link(&url) => (delivery_id, url),
+        Ok(_) | Err(MagicLinkError::Invalid) => return Ok(HttpResponse::NotFound().finish()),
+        Err(MagicLinkError::Expired) => return Ok(HttpResponse::Gone().finish()),
+    };
+    record_click(&db_pool, delivery_id, &url, user_agent(&request))
+        .await
+        .context("Failed to record a link click.")?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .finish())
+}
+
+fn user_agent(request: &HttpRequest) -> Option<&str> {
+    request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+}
+
+#[tracing::instrument(name = "Recording an email open", skip(db_pool))]
+async fn record_open(
+    db_pool: &PgPool,
+    delivery_id: Uuid,
+    user_agent: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_opens (delivery_id, opened_at, user_agent)
+        SELECT delivery_id, now(), $2 FROM newsletter_deliveries
+        WHERE delivery_id = $1 AND tracked
+        "#,
+        delivery_id,
+        user_agent
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Recording a link click", skip(db_pool))]
+async fn record_click(
+    db_pool: &PgPool,
+    delivery_id: Uuid,
+    url: &str,
+    user_agent: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO link_clicks (delivery_id, url, clicked_at, user_agent)
+        SELECT delivery_id, $2, now(), $3 FROM newsletter_deliveries
+        WHERE delivery_id = $1
+        "#,
+        delivery_id,
+        url,
+        user_agent
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
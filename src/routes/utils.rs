@@ -0,0 +1,35 @@
+use crate::routes::ApiError;
+use actix_web::http::header::{ContentType, LOCATION};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use anyhow::Context;
+use tera::Tera;
+
+pub fn see_other(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+pub fn render_html(
+    templates: &Tera,
+    template_name: &str,
+    context: &tera::Context,
+) -> Result<HttpResponse, ApiError> {
+    render_html_with_status(templates, template_name, context, StatusCode::OK)
+}
+
+pub fn render_html_with_status(
+    templates: &Tera,
+    template_name: &str,
+    context: &tera::Context,
+    status: StatusCode,
+) -> Result<HttpResponse, ApiError> {
+    let body = templates
+        .render(template_name, context)
+        .with_context(|| format!("Failed to render the {} template.", template_name))?;
+
+    Ok(HttpResponse::build(status)
+        .content_type(ContentType::html())
+        .body(body))
+}
@@ -0,0 +1,243 @@
+use crate::domain::SubscriptionStatus;
+use crate::email_client::SendGridEventVerifier;
+use crate::routes::subscriptions_unsubscribe::mark_subscriber_as_unsubscribed;
+use crate::routes::ApiError;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const SIGNATURE_HEADER: &str = "X-Twilio-Email-Event-Webhook-Signature";
+const TIMESTAMP_HEADER: &str = "X-Twilio-Email-Event-Webhook-Timestamp";
+
+#[derive(serde::Deserialize, Debug)]
+struct SendGridEvent {
+    sg_event_id: String,
+    event: String,
+    email: String,
+    timestamp: i64,
+    #[serde(default, rename = "type")]
+    bounce_type: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum EventEffect {
+    Suppress,
+    Unsubscribe,
+    RecordOnly,
+}
+
+impl SendGridEvent {
+    fn occurred_at(&self) -> Option<DateTime<Utc>> {
+        Utc.timestamp_opt(self.timestamp, 0).single()
+    }
+
+    fn effect(&self) -> Option<EventEffect> {
+        match self.event.as_str() {
+            "bounce" if self.bounce_type.as_deref() == Some("blocked") => {
+                Some(EventEffect::RecordOnly)
+            }
+            "bounce" | "spamreport" => Some(EventEffect::Suppress),
+            "unsubscribe" => Some(EventEffect::Unsubscribe),
+            "dropped" => Some(EventEffect::RecordOnly),
+            _ => None,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Receiving SendGrid events", skip_all)]
+pub async fn sendgrid_events(
+    request: HttpRequest,
+    body: Bytes,
+    verifier: web::Data<Option<SendGridEventVerifier>>,
+    db_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let verifier = match verifier.as_ref() {
+        Some(verifier) => verifier,
+        None => {
+            tracing::warn!("Rejecting SendGrid events, no verification key is configured.");
+            return Ok(HttpResponse::Unauthorized().finish());
+        }
+    };
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let verification = match (header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER)) {
+        (Some(timestamp), Some(signature)) => verifier.verify(timestamp, &body, signature),
+        _ => Err(anyhow::anyhow!("The signature headers are missing.")),
+    };
+    if let Err(e) = verification {
+        tracing::warn!(error.cause_chain = ?e, "Rejecting SendGrid events with an invalid signature.");
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    let events: Vec<SendGridEvent> = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::ValidationError(format!("Invalid SendGrid events: {}", e)))?;
+    // Checked upfront, a malformed batch is rejected as a whole.
+    let events = events
+        .into_iter()
+        .map(|event| match event.occurred_at() {
+            Some(occurred_at) => Ok((event, occurred_at)),
+            None => Err(ApiError::ValidationError(format!(
+                "Invalid timestamp of the SendGrid event {}: {}",
+                event.sg_event_id, event.timestamp
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for (event, occurred_at) in events {
+        let effect = match event.effect() {
+            Some(effect) => effect,
+            None => continue,
+        };
+        process_event(&db_pool, &event, occurred_at, effect).await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    name = "Processing a SendGrid event",
+    skip(db_pool, event),
+    fields(event_id = %event.sg_event_id, event_type = %event.event)
+)]
+async fn process_event(
+    db_pool: &PgPool,
+    event: &SendGridEvent,
+    occurred_at: DateTime<Utc>,
+    effect: EventEffect,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire database connection from the pool.")?;
+    let subscriber_id = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1)"#,
+        event.email
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to find the subscriber of the event.")?
+    .map(|row| row.id);
+    let subscriber_id = match subscriber_id {
+        Some(subscriber_id) => subscriber_id,
+        None => return Ok(()),
+    };
+    if !record_event(&mut transaction, event, occurred_at, subscriber_id).await? {
+        return Ok(());
+    }
+    if effect == EventEffect::Suppress {
+        suppress_subscriber(&mut transaction, subscriber_id).await?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a SendGrid event.")?;
+
+    if effect == EventEffect::Unsubscribe {
+        mark_subscriber_as_unsubscribed(db_pool, subscriber_id)
+            .await
+            .context("Failed to unsubscribe the subscriber of the event.")?;
+    }
+    Ok(())
+}
+
+async fn record_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &SendGridEvent,
+    occurred_at: DateTime<Utc>,
+    subscriber_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO email_events (event_id, subscriber_id, event_type, reason, occurred_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (event_id) DO NOTHING
+        "#,
+        event.sg_event_id,
+        subscriber_id,
+        event.event,
+        event.reason,
+        occurred_at
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to record the SendGrid event.")?
+    .rows_affected();
+
+    Ok(inserted > 0)
+}
+
+/// Unsubscribed subscribers stay so.
+async fn suppress_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2
+        WHERE id = $1 AND status <> $3
+        "#,
+        subscriber_id,
+        SubscriptionStatus::Suppressed.as_str(),
+        SubscriptionStatus::Unsubscribed.as_str()
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to suppress the subscriber.")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventEffect, SendGridEvent};
+
+    fn event(event: &str, bounce_type: Option<&str>) -> SendGridEvent {
+        SendGridEvent {
+            sg_event_id: "event-id".into(),
+            event: event.into(),
+            email: "ursula_le_guin@gmail.com".into(),
+            timestamp: 1656666000,
+            bounce_type: bounce_type.map(String::from),
+            reason: None,
+        }
+    }
+
+    #[test]
+    fn hard_bounces_and_complaints_suppress_the_subscriber() {
+        assert_eq!(
+            event("bounce", Some("bounce")).effect(),
+            Some(EventEffect::Suppress)
+        );
+        assert_eq!(
+            event("spamreport", None).effect(),
+            Some(EventEffect::Suppress)
+        );
+        assert_eq!(
+            event("bounce", Some("blocked")).effect(),
+            Some(EventEffect::RecordOnly)
+        );
+    }
+
+    #[test]
+    fn out_of_range_timestamps_have_no_date() {
+        let mut event = event("bounce", Some("bounce"));
+        assert!(event.occurred_at().is_some());
+        event.timestamp = i64::MAX;
+        assert!(event.occurred_at().is_none());
+    }
+
+    #[test]
+    fn engagement_events_are_ignored() {
+        for name in ["processed", "delivered", "open", "click"] {
+            assert_eq!(event(name, None).effect(), None);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::configuration::SessionSettings;
+use crate::session::state::SessionStatus;
+use crate::session::{Session, SessionState, SessionStore};
+use actix_web::body::MessageBody;
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{Error, HttpMessage};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub struct SessionMiddleware {
+    store: Arc<dyn SessionStore>,
+    settings: Rc<SessionSettings>,
+}
+
+impl SessionMiddleware {
+    pub fn new(store: Arc<dyn SessionStore>, settings: SessionSettings) -> Self {
+        Self {
+            store,
+            settings: Rc::new(settings),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SessionMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = InnerSessionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(InnerSessionMiddleware {
+            service: Rc::new(service),
+            store: self.store.clone(),
+            settings: self.settings.clone(),
+        }))
+    }
+}
+
+pub struct InnerSessionMiddleware<S> {
+    service: Rc<S>,
+    store: Arc<dyn SessionStore>,
+    settings: Rc<SessionSettings>,
+}
+
+impl<S, B> Service<ServiceRequest> for InnerSessionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let store = self.store.clone();
+        let settings = self.settings.clone();
+
+        Box::pin(async move {
+            let session_key = req
+                .cookie(&settings.cookie_name)
+                .map(|c| c.value().to_string());
+            let (session_key, state) = match session_key {
+                Some(session_key) => match store
+                    .load(&session_key)
+                    .await
+                    .map_err(ErrorInternalServerError)?
+                {
+                    Some(state) => (Some(session_key), state),
+                    None => (None, SessionState::new()),
+                },
+                None => (None, SessionState::new()),
+            };
+            Session::set_session(&mut req.extensions_mut(), state);
+
+            let mut res = service.call(req).await?;
+
+            let (status, state) = Session::get_changes(&res.request().extensions());
+            let ttl = chrono::Duration::minutes(settings.ttl_minutes);
+            if let (SessionStatus::Renewed | SessionStatus::Purged, Some(session_key)) =
+                (status, &session_key)
+            {
+                store
+                    .delete(session_key)
+                    .await
+                    .map_err(ErrorInternalServerError)?;
+            }
+            let cookie = match (status, session_key) {
+                (SessionStatus::Unchanged, _) => None,
+                (SessionStatus::Changed, Some(session_key)) => {
+                    store
+                        .update(&session_key, state, ttl)
+                        .await
+                        .map_err(ErrorInternalServerError)?;
+                    Some(session_cookie(&settings, session_key))
+                }
+                (SessionStatus::Changed, None) | (SessionStatus::Renewed, _) => {
+                    let session_key = store
+                        .save(state, ttl)
+                        .await
+                        .map_err(ErrorInternalServerError)?;
+                    Some(session_cookie(&settings, session_key))
+                }
+                (SessionStatus::Purged, _) => {
+                    let mut removal_cookie = session_cookie(&settings, String::new());
+                    removal_cookie.make_removal();
+                    Some(removal_cookie)
+                }
+            };
+            if let Some(cookie) = cookie {
+                res.response_mut()
+                    .add_cookie(&cookie)
+                    .map_err(ErrorInternalServerError)?;
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+fn session_cookie(settings: &SessionSettings, session_key: String) -> Cookie<'static> {
+    Cookie::build(settings.cookie_name.clone(), session_key)
+        .path("/")
+        .http_only(true)
+        .secure(settings.secure_cookie)
+        .same_site(SameSite::Lax)
+        .max_age(CookieDuration::minutes(settings.ttl_minutes))
+        .finish()
+}
@@ -0,0 +1,7 @@
+mod middleware;
+mod state;
+mod store;
+
+pub use middleware::SessionMiddleware;
+pub use state::{FlashLevel, FlashMessage, Session, TypedSession};
+pub use store::{InMemorySessionStore, PostgresSessionStore, SessionState, SessionStore};
@@ -0,0 +1,177 @@
+use crate::session::SessionState;
+use actix_web::dev::{Extensions, Payload};
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum SessionStatus {
+    #[default]
+    Unchanged,
+    Changed,
+    Renewed,
+    Purged,
+}
+
+#[derive(Default)]
+struct SessionInner {
+    state: SessionState,
+    status: SessionStatus,
+}
+
+#[derive(Clone)]
+pub struct Session(Rc<RefCell<SessionInner>>);
+
+impl Session {
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, serde_json::Error> {
+        match self.0.borrow().state.get(key) {
+            Some(value) => Ok(Some(serde_json::from_str(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn insert<T: Serialize>(&self, key: &str, value: T) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_string(&value)?;
+        let mut inner = self.0.borrow_mut();
+        inner.state.insert(key.to_string(), value);
+        inner.status = match inner.status {
+            SessionStatus::Unchanged => SessionStatus::Changed,
+            SessionStatus::Purged => SessionStatus::Renewed,
+            status => status,
+        };
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut inner = self.0.borrow_mut();
+        let removed = inner.state.remove(key);
+        if removed.is_some() && inner.status == SessionStatus::Unchanged {
+            inner.status = SessionStatus::Changed;
+        }
+        removed
+    }
+
+    pub fn renew(&self) {
+        self.0.borrow_mut().status = SessionStatus::Renewed;
+    }
+
+    pub fn purge(&self) {
+        let mut inner = self.0.borrow_mut();
+        inner.state.clear();
+        inner.status = SessionStatus::Purged;
+    }
+
+    pub(crate) fn set_session(extensions: &mut Extensions, state: SessionState) {
+        let inner = SessionInner {
+            state,
+            status: SessionStatus::Unchanged,
+        };
+        extensions.insert(Session(Rc::new(RefCell::new(inner))));
+    }
+
+    fn from_extensions(extensions: &Extensions) -> Self {
+        extensions
+            .get::<Session>()
+            .cloned()
+            .unwrap_or_else(|| Session(Rc::new(RefCell::new(SessionInner::default()))))
+    }
+
+    pub(crate) fn get_changes(extensions: &Extensions) -> (SessionStatus, SessionState) {
+        match extensions.get::<Session>() {
+            Some(session) => {
+                let inner = session.0.borrow();
+                (inner.status, inner.state.clone())
+            }
+            None => (SessionStatus::Unchanged, SessionState::new()),
+        }
+    }
+}
+
+impl FromRequest for Session {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(Session::from_extensions(&req.extensions())))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashLevel {
+    Info,
+    Error,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct FlashMessage {
+    pub level: FlashLevel,
+    pub content: String,
+}
+
+impl FlashMessage {
+    pub fn info(content: impl Into<String>) -> Self {
+        Self {
+            level: FlashLevel::Info,
+            content: content.into(),
+        }
+    }
+
+    pub fn error(content: impl Into<String>) -> Self {
+        Self {
+            level: FlashLevel::Error,
+            content: content.into(),
+        }
+    }
+}
+
+pub struct TypedSession(Session);
+
+impl TypedSession {
+    const USER_ID_KEY: &'static str = "user_id";
+    const FLASH_MESSAGES_KEY: &'static str = "flash_messages";
+
+    pub fn renew(&self) {
+        self.0.renew();
+    }
+
+    pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), serde_json::Error> {
+        self.0.insert(Self::USER_ID_KEY, user_id)
+    }
+
+    pub fn get_user_id(&self) -> Result<Option<Uuid>, serde_json::Error> {
+        self.0.get(Self::USER_ID_KEY)
+    }
+
+    pub fn log_out(&self) {
+        self.0.purge();
+    }
+
+    pub fn add_flash_message(&self, message: FlashMessage) -> Result<(), serde_json::Error> {
+        let mut messages: Vec<FlashMessage> =
+            self.0.get(Self::FLASH_MESSAGES_KEY)?.unwrap_or_default();
+        messages.push(message);
+        self.0.insert(Self::FLASH_MESSAGES_KEY, messages)
+    }
+
+    pub fn take_flash_messages(&self) -> Result<Vec<FlashMessage>, serde_json::Error> {
+        let messages = self.0.get(Self::FLASH_MESSAGES_KEY)?.unwrap_or_default();
+        self.0.remove(Self::FLASH_MESSAGES_KEY);
+        Ok(messages)
+    }
+}
+
+impl FromRequest for TypedSession {
+    type Error = <Session as FromRequest>::Error;
+    type Future = Ready<Result<TypedSession, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(TypedSession(Session::from_extensions(
+            &req.extensions(),
+        ))))
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub type SessionState = HashMap<String, String>;
+
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn load(&self, session_key: &str) -> Result<Option<SessionState>, anyhow::Error>;
+
+    async fn save(&self, state: SessionState, ttl: Duration) -> Result<String, anyhow::Error>;
+
+    async fn update(
+        &self,
+        session_key: &str,
+        state: SessionState,
+        ttl: Duration,
+    ) -> Result<(), anyhow::Error>;
+
+    async fn delete(&self, session_key: &str) -> Result<(), anyhow::Error>;
+}
+
+fn generate_session_key() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(64)
+        .collect()
+}
+
+pub struct PostgresSessionStore {
+    db_pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Load session state", skip(self, session_key))]
+    async fn load(&self, session_key: &str) -> Result<Option<SessionState>, anyhow::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT state
+            FROM sessions
+            WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Failed to load the session state.")?;
+
+        match row {
+            Some(r) => Ok(Some(
+                serde_json::from_str(&r.state).context("Failed to deserialize session state.")?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    #[tracing::instrument(name = "Save session state", skip(self, state))]
+    async fn save(&self, state: SessionState, ttl: Duration) -> Result<String, anyhow::Error> {
+        let session_key = generate_session_key();
+        let state = serde_json::to_string(&state).context("Failed to serialize session state.")?;
+
+        sqlx::query!(r#"DELETE FROM sessions WHERE expires_at <= now()"#)
+            .execute(&self.db_pool)
+            .await
+            .context("Failed to delete expired sessions.")?;
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (session_key, state, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            session_key,
+            state,
+            Utc::now() + ttl
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Failed to save the session state.")?;
+
+        Ok(session_key)
+    }
+
+    #[tracing::instrument(name = "Update session state", skip(self, session_key, state))]
+    async fn update(
+        &self,
+        session_key: &str,
+        state: SessionState,
+        ttl: Duration,
+    ) -> Result<(), anyhow::Error> {
+        let state = serde_json::to_string(&state).context("Failed to serialize session state.")?;
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET state = $2, expires_at = $3
+            WHERE session_key = $1
+            "#,
+            session_key,
+            state,
+            Utc::now() + ttl
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Failed to update the session state.")?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Delete session state", skip(self, session_key))]
+    async fn delete(&self, session_key: &str) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE session_key = $1"#,
+            session_key
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Failed to delete the session state.")?;
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct InMemorySessionStore {
+    sessions: Mutex<HashMap<String, (SessionState, DateTime<Utc>)>>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for InMemorySessionStore {
+    async fn load(&self, session_key: &str) -> Result<Option<SessionState>, anyhow::Error> {
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions
+            .get(session_key)
+            .filter(|(_, expires_at)| *expires_at > Utc::now())
+            .map(|(state, _)| state.clone()))
+    }
+
+    async fn save(&self, state: SessionState, ttl: Duration) -> Result<String, anyhow::Error> {
+        let session_key = generate_session_key();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, (_, expires_at)| *expires_at > Utc::now());
+        sessions.insert(session_key.clone(), (state, Utc::now() + ttl));
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: &str,
+        state: SessionState,
+        ttl: Duration,
+    ) -> Result<(), anyhow::Error> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session_key.to_string(), (state, Utc::now() + ttl));
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &str) -> Result<(), anyhow::Error> {
+        self.sessions.lock().unwrap().remove(session_key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session::{InMemorySessionStore, SessionState, SessionStore};
+    use chrono::Duration;
+    use claim::{assert_none, assert_some};
+
+    fn state() -> SessionState {
+        let mut state = SessionState::new();
+        state.insert("user_id".into(), "\"42\"".into());
+        state
+    }
+
+    #[tokio::test]
+    async fn saved_session_can_be_loaded() {
+        let store = InMemorySessionStore::default();
+
+        let session_key = store.save(state(), Duration::minutes(5)).await.unwrap();
+
+        let loaded = store.load(&session_key).await.unwrap();
+        assert_some!(&loaded);
+        assert_eq!(loaded.unwrap(), state());
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_not_loaded() {
+        let store = InMemorySessionStore::default();
+
+        let session_key = store.save(state(), Duration::minutes(-1)).await.unwrap();
+
+        assert_none!(store.load(&session_key).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn deleted_session_is_not_loaded() {
+        let store = InMemorySessionStore::default();
+        let session_key = store.save(state(), Duration::minutes(5)).await.unwrap();
+
+        store.delete(&session_key).await.unwrap();
+
+        assert_none!(store.load(&session_key).await.unwrap());
+    }
+}
@@ -7,17 +7,41 @@ use sqlx::postgres::PgPoolOptions;
 use sqlx::{PgPool, Pool, Postgres};
 
 use crate::configuration::{
-    DatabaseSettings, EmailClientSettings, Settings, TemplateEngineSettings,
+    DatabaseSettings, EmailClientSettings, EmailProvider, Environment, RateLimitSettings,
+    RateLimitStoreKind, SessionSettings, SessionStoreKind, Settings, TemplateEngineSettings,
+};
+use crate::confirmation_email_worker;
+use crate::email_client::{
+    EmailClient, EmailTransport, InMemoryTransport, Mailbox, OutboxTransport, PostmarkTransport,
+    SendGridEventVerifier, SendGridTransport, SmtpTransport,
+};
+use crate::issue_delivery_worker::run_worker_until_stopped;
+use crate::newsletter_scheduler::run_scheduler_until_stopped;
+use crate::rate_limit::{
+    run_cleanup_until_stopped, InMemoryRateLimitStore, PostgresRateLimitStore, RateLimitMiddleware,
+    RateLimitStore, RateLimiter,
 };
-use crate::email_client::EmailClient;
 use crate::routes;
+use crate::session::{InMemorySessionStore, PostgresSessionStore, SessionMiddleware, SessionStore};
+use crate::subscriber_links::SubscriberLinks;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 use tera::Tera;
 use tracing_actix_web::TracingLogger;
 
 pub struct ApplicationBaseUrl(pub String);
 
+pub struct ConfirmationEmailRateLimiter(pub RateLimiter);
+
+type BackgroundTask = Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>>;
+
 pub struct Application {
     server: Server,
+    delivery_worker: BackgroundTask,
+    scheduler: BackgroundTask,
+    confirmation_worker: BackgroundTask,
+    rate_limit_cleanup: BackgroundTask,
     port: u16,
 }
 
@@ -27,9 +51,10 @@ impl Application {
         let db_connection_pool = create_db_connection_pool(&configuration.database).await;
         migrate_db(&db_connection_pool).await;
 
-        let email_client = create_email_client(&configuration.email_client);
+        let mailbox = Mailbox::default();
+        let email_client = create_email_client(&configuration.email_client, &mailbox);
         let templates = create_template_engine(&configuration.template_engine);
-        let base_url = &configuration.application.base_url;
+        let session_store = create_session_store(&configuration.session, &db_connection_pool);
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
@@ -41,44 +66,249 @@ impl Application {
             db_connection_pool,
             email_client,
             templates,
-            base_url,
+            session_store,
+            mailbox,
+            configuration,
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn initialize(
         tcp_listener: TcpListener,
         connection_pool: PgPool,
         email_client: EmailClient,
         templates: Tera,
-        base_url: &str,
+        session_store: Arc<dyn SessionStore>,
+        mailbox: Mailbox,
+        configuration: &Settings,
     ) -> Result<Self, std::io::Error> {
+        let rate_limit_store = create_rate_limit_store(&configuration.rate_limit, &connection_pool);
+        let client_ip_rate_limiter = RateLimiter::new(
+            rate_limit_store.clone(),
+            configuration
+                .rate_limit
+                .subscriptions_per_client_ip
+                .bucket(),
+        );
+        let confirmation_email_rate_limiter =
+            web::Data::new(ConfirmationEmailRateLimiter(RateLimiter::new(
+                rate_limit_store,
+                configuration
+                    .rate_limit
+                    .confirmation_emails_per_address
+                    .bucket(),
+            )));
         let connection_pool = web::Data::new(connection_pool);
         let email_client = web::Data::new(email_client);
-        let base_url = web::Data::new(ApplicationBaseUrl(base_url.to_string()));
+        let base_url = web::Data::new(ApplicationBaseUrl(
+            configuration.application.base_url.clone(),
+        ));
+        let mailbox = web::Data::new(mailbox);
+        let subscription_settings = web::Data::new(configuration.subscriptions.clone());
+        let branding = web::Data::new(configuration.branding.clone());
+        let subscriber_links = web::Data::new(SubscriberLinks::from_settings(configuration));
+        let sendgrid_event_verifier = web::Data::new(
+            configuration
+                .email_client
+                .sendgrid_webhook
+                .as_ref()
+                .map(|webhook| {
+                    SendGridEventVerifier::new(&webhook.verification_key)
+                        .expect("Invalid SendGrid webhook verification key.")
+                }),
+        );
+        let session_settings = configuration.session.clone();
+        let environment = configuration.environment;
         let templates = web::Data::new(templates);
 
         let port = tcp_listener.local_addr().unwrap().port();
+        let delivery_worker = Box::pin(run_worker_until_stopped(
+            connection_pool.get_ref().clone(),
+            email_client.clone().into_inner(),
+            templates.clone().into_inner(),
+            subscriber_links.clone().into_inner(),
+        ));
+        let scheduler = Box::pin(run_scheduler_until_stopped(
+            connection_pool.get_ref().clone(),
+        ));
+        let confirmation_worker = Box::pin(confirmation_email_worker::run_worker_until_stopped(
+            connection_pool.get_ref().clone(),
+            email_client.clone().into_inner(),
+            templates.clone().into_inner(),
+            base_url.0.clone(),
+        ));
+        let rate_limit_cleanup =
+            Box::pin(run_cleanup_until_stopped(connection_pool.get_ref().clone()));
 
         let server = HttpServer::new(move || {
             App::new()
+                .wrap(SessionMiddleware::new(
+                    session_store.clone(),
+                    session_settings.clone(),
+                ))
                 .wrap(TracingLogger::default())
                 .route("/health", web::get().to(routes::health_check))
+                .route("/login", web::get().to(routes::login_form))
+                .route("/login", web::post().to(routes::login))
+                .service(
+                    web::scope("/admin")
+                        .route("/dashboard", web::get().to(routes::admin_dashboard))
+                        .route("/password", web::get().to(routes::change_password_form))
+                        .route("/password", web::post().to(routes::change_password_submit))
+                        .route("/logout", web::post().to(routes::log_out))
+                        .route("/subscribers", web::get().to(routes::list_subscribers))
+                        .route(
+                            "/subscribers/export.csv",
+                            web::get().to(routes::export_subscribers),
+                        )
+                        .service(
+                            web::resource("/subscribers/import")
+                                .app_data(web::PayloadConfig::new(routes::IMPORT_SIZE_LIMIT))
+                                .route(web::post().to(routes::import_subscribers)),
+                        )
+                        .route(
+                            "/subscribers/{subscriber_id}",
+                            web::get().to(routes::get_subscriber),
+                        )
+                        .route(
+                            "/subscribers/{subscriber_id}",
+                            web::patch().to(routes::update_subscriber),
+                        )
+                        .route(
+                            "/subscribers/{subscriber_id}",
+                            web::delete().to(routes::delete_subscriber),
+                        )
+                        .route(
+                            "/subscribers/export",
+                            web::post().to(routes::export_subscriber_data),
+                        )
+                        .route(
+                            "/subscribers/erase",
+                            web::post().to(routes::erase_subscriber_data),
+                        ),
+                )
                 .route(
                     "/newsletters",
                     web::post().to(routes::distribute_newsletter),
                 )
-                .route("/subscriptions", web::post().to(routes::subscribe))
-                .route("/subscriptions/confirm", web::get().to(routes::confirm))
+                .route(
+                    "/newsletters/preview",
+                    web::post().to(routes::preview_newsletter),
+                )
+                .route(
+                    "/newsletters/test",
+                    web::post().to(routes::send_test_newsletter),
+                )
+                .route(
+                    "/newsletters/scheduled",
+                    web::get().to(routes::list_scheduled_newsletters),
+                )
+                .route(
+                    "/newsletters/scheduled/{newsletter_issue_id}",
+                    web::patch().to(routes::reschedule_newsletter),
+                )
+                .route(
+                    "/newsletters/scheduled/{newsletter_issue_id}",
+                    web::delete().to(routes::cancel_newsletter),
+                )
+                .route("/lists", web::get().to(routes::list_lists))
+                .route("/lists", web::post().to(routes::create_list))
+                .route("/lists/{slug}", web::patch().to(routes::update_list))
+                .route("/issues", web::get().to(routes::list_issues))
+                .route("/issues/{slug}", web::get().to(routes::view_issue))
+                .service(
+                    web::resource("/subscriptions")
+                        .wrap(RateLimitMiddleware::per_client_ip(
+                            client_ip_rate_limiter.clone(),
+                        ))
+                        .route(web::post().to(routes::subscribe)),
+                )
+                .route(
+                    "/subscriptions/confirm",
+                    web::get().to(routes::confirm_form),
+                )
+                .route("/subscriptions/confirm", web::post().to(routes::confirm))
+                .service(
+                    web::resource("/subscriptions/confirm/resend")
+                        .wrap(RateLimitMiddleware::per_client_ip(
+                            client_ip_rate_limiter.clone(),
+                        ))
+                        .route(web::post().to(routes::resend_confirmation)),
+                )
+                .route(
+                    "/subscriptions/preferences",
+                    web::get().to(routes::preferences_form),
+                )
+                .route(
+                    "/subscriptions/preferences",
+                    web::post().to(routes::update_preferences),
+                )
+                .route(
+                    "/subscriptions/data",
+                    web::get().to(routes::data_request_form),
+                )
+                .service(
+                    web::resource("/subscriptions/data")
+                        .wrap(RateLimitMiddleware::per_client_ip(
+                            client_ip_rate_limiter.clone(),
+                        ))
+                        .route(web::post().to(routes::request_data)),
+                )
+                .route(
+                    "/subscriptions/data/export",
+                    web::get().to(routes::export_data),
+                )
+                .route(
+                    "/subscriptions/data/erase",
+                    web::get().to(routes::erase_data_form),
+                )
+                .route(
+                    "/subscriptions/data/erase",
+                    web::post().to(routes::erase_data),
+                )
+                .route(
+                    "/subscriptions/unsubscribe",
+                    web::get().to(routes::unsubscribe_form),
+                )
+                .route(
+                    "/subscriptions/unsubscribe",
+                    web::post().to(routes::unsubscribe),
+                )
+                .route("/t/o/{delivery_id}.gif", web::get().to(routes::track_open))
+                .route("/t/c/{token}", web::get().to(routes::track_click))
+                .route(
+                    "/webhooks/sendgrid",
+                    web::post().to(routes::sendgrid_events),
+                )
+                .configure(|cfg| {
+                    // Exposes every captured email, including confirmation links - never in production.
+                    if environment == Environment::Local {
+                        cfg.route("/dev/mailbox", web::get().to(routes::dev_mailbox));
+                    }
+                })
                 .service(actix_files::Files::new("/", "./static"))
                 .app_data(connection_pool.clone())
                 .app_data(email_client.clone())
                 .app_data(base_url.clone())
                 .app_data(templates.clone())
+                .app_data(mailbox.clone())
+                .app_data(subscription_settings.clone())
+                .app_data(branding.clone())
+                .app_data(confirmation_email_rate_limiter.clone())
+                .app_data(subscriber_links.clone())
+                .app_data(sendgrid_event_verifier.clone())
         })
         .listen(tcp_listener)?
         .run();
 
-        Ok(Self { server, port })
+        Ok(Self {
+            server,
+            delivery_worker,
+            scheduler,
+            confirmation_worker,
+            rate_limit_cleanup,
+            port,
+        })
     }
 
     pub fn get_port(&self) -> u16 {
@@ -86,7 +316,16 @@ impl Application {
     }
 
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+        let delivery_worker = tokio::spawn(self.delivery_worker);
+        let scheduler = tokio::spawn(self.scheduler);
+        let confirmation_worker = tokio::spawn(self.confirmation_worker);
+        let rate_limit_cleanup = tokio::spawn(self.rate_limit_cleanup);
+        let outcome = self.server.await;
+        delivery_worker.abort();
+        scheduler.abort();
+        confirmation_worker.abort();
+        rate_limit_cleanup.abort();
+        outcome
     }
 }
 
@@ -101,15 +340,67 @@ pub fn create_template_engine(settings: &TemplateEngineSettings) -> Tera {
     }
 }
 
-#[tracing::instrument(name = "Creating Email Client")]
-pub fn create_email_client(config: &EmailClientSettings) -> EmailClient {
+#[tracing::instrument(name = "Creating Session Store", skip(db_connection_pool))]
+pub fn create_session_store(
+    settings: &SessionSettings,
+    db_connection_pool: &PgPool,
+) -> Arc<dyn SessionStore> {
+    match settings.store {
+        SessionStoreKind::Postgres => {
+            Arc::new(PostgresSessionStore::new(db_connection_pool.clone()))
+        }
+        SessionStoreKind::InMemory => Arc::new(InMemorySessionStore::default()),
+    }
+}
+
+#[tracing::instrument(name = "Creating Rate Limit Store", skip(db_connection_pool))]
+pub fn create_rate_limit_store(
+    settings: &RateLimitSettings,
+    db_connection_pool: &PgPool,
+) -> Arc<dyn RateLimitStore> {
+    match settings.store {
+        RateLimitStoreKind::Postgres => {
+            Arc::new(PostgresRateLimitStore::new(db_connection_pool.clone()))
+        }
+        RateLimitStoreKind::InMemory => Arc::new(InMemoryRateLimitStore::default()),
+    }
+}
+
+#[tracing::instrument(name = "Creating Email Client", skip(mailbox))]
+pub fn create_email_client(config: &EmailClientSettings, mailbox: &Mailbox) -> EmailClient {
     let sender_email = config.sender().expect("Invalid sender email address");
-    EmailClient::new(
-        config.base_url.clone(),
-        sender_email,
-        config.api_key.clone(),
-        Duration::from_millis(config.timeout_millis),
-    )
+    let timeout = Duration::from_millis(config.timeout_millis);
+    let transport: Box<dyn EmailTransport> = match config.provider {
+        EmailProvider::SendGrid => Box::new(SendGridTransport::new(
+            config.base_url.clone(),
+            config.api_key.clone(),
+            timeout,
+        )),
+        EmailProvider::Postmark => Box::new(PostmarkTransport::new(
+            config.base_url.clone(),
+            config.api_key.clone(),
+            timeout,
+        )),
+        EmailProvider::Smtp => {
+            let smtp_settings = config
+                .smtp
+                .as_ref()
+                .expect("The 'smtp' email provider requires the 'email_client.smtp' settings.");
+            Box::new(
+                SmtpTransport::new(smtp_settings, timeout)
+                    .expect("Failed to create the SMTP transport."),
+            )
+        }
+        EmailProvider::Outbox => {
+            let outbox_settings = config
+                .outbox
+                .as_ref()
+                .expect("The 'outbox' email provider requires the 'email_client.outbox' settings.");
+            Box::new(OutboxTransport::new(&outbox_settings.directory))
+        }
+        EmailProvider::InMemory => Box::new(InMemoryTransport::new(mailbox.clone())),
+    };
+    EmailClient::new(transport, sender_email, config.retry_policy())
 }
 
 #[tracing::instrument(name = "Creating DB connection pool")]
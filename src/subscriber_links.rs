@@ -0,0 +1,125 @@
+use crate::configuration::Settings;
+use crate::magic_link::{MagicLinkError, MagicLinkSigner};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+const PREFERENCES: &str = "preferences";
+const DATA_EXPORT: &str = "data_export";
+const DATA_ERASURE: &str = "data_erasure";
+const CLICK: &str = "click";
+
+/// Issues are read long after they were sent, their links should keep working as long.
+const CLICK_LINK_TTL_DAYS: i64 = 5 * 365;
+
+pub struct SubscriberLinks {
+    base_url: String,
+    signer: MagicLinkSigner,
+    preferences_link_ttl: Duration,
+    data_request_link_ttl: Duration,
+}
+
+impl SubscriberLinks {
+    pub fn new(
+        base_url: String,
+        signer: MagicLinkSigner,
+        preferences_link_ttl: Duration,
+        data_request_link_ttl: Duration,
+    ) -> Self {
+        Self {
+            base_url,
+            signer,
+            preferences_link_ttl,
+            data_request_link_ttl,
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            settings.application.base_url.clone(),
+            MagicLinkSigner::new(settings.application.hmac_secret.clone()),
+            settings.subscriptions.preferences_link_ttl(),
+            settings.subscriptions.data_request_link_ttl(),
+        )
+    }
+
+    pub fn unsubscribe(&self, unsubscribe_token: &str) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            self.base_url, unsubscribe_token
+        )
+    }
+
+    pub fn view_in_browser(&self, issue_slug: &str) -> String {
+        format!("{}/issues/{}", self.base_url, issue_slug)
+    }
+
+    pub fn open_tracking_pixel(&self, delivery_id: Uuid) -> String {
+        format!("{}/t/o/{}.gif", self.base_url, delivery_id)
+    }
+
+    pub fn click_tracking(&self, delivery_id: Uuid, url: &str) -> String {
+        let token = self.signer.sign(
+            CLICK,
+            &format!("{} {}", delivery_id, url),
+            Utc::now() + Duration::days(CLICK_LINK_TTL_DAYS),
+        );
+        format!("{}/t/c/{}", self.base_url, token)
+    }
+
+    pub fn verify_click_token(&self, token: &str) -> Result<(Uuid, String), MagicLinkError> {
+        let subject = self.signer.verify(CLICK, token)?;
+        let (delivery_id, url) = subject.split_once(' ').ok_or(MagicLinkError::Invalid)?;
+        let delivery_id = Uuid::parse_str(delivery_id).map_err(|_| MagicLinkError::Invalid)?;
+        Ok((delivery_id, url.to_string()))
+    }
+
+    pub fn preferences(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/preferences?token={}",
+            self.base_url,
+            self.preferences_token(subscriber_id)
+        )
+    }
+
+    pub fn preferences_token(&self, subscriber_id: Uuid) -> String {
+        self.sign(PREFERENCES, subscriber_id, self.preferences_link_ttl)
+    }
+
+    pub fn verify_preferences_token(&self, token: &str) -> Result<Uuid, MagicLinkError> {
+        self.verify(PREFERENCES, token)
+    }
+
+    pub fn data_export(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/data/export?token={}",
+            self.base_url,
+            self.sign(DATA_EXPORT, subscriber_id, self.data_request_link_ttl)
+        )
+    }
+
+    pub fn verify_data_export_token(&self, token: &str) -> Result<Uuid, MagicLinkError> {
+        self.verify(DATA_EXPORT, token)
+    }
+
+    pub fn data_erasure(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/data/erase?token={}",
+            self.base_url,
+            self.sign(DATA_ERASURE, subscriber_id, self.data_request_link_ttl)
+        )
+    }
+
+    pub fn verify_data_erasure_token(&self, token: &str) -> Result<Uuid, MagicLinkError> {
+        self.verify(DATA_ERASURE, token)
+    }
+
+    fn sign(&self, purpose: &str, subscriber_id: Uuid, ttl: Duration) -> String {
+        self.signer
+            .sign(purpose, &subscriber_id.to_string(), Utc::now() + ttl)
+    }
+
+    fn verify(&self, purpose: &str, token: &str) -> Result<Uuid, MagicLinkError> {
+        let subject = self.signer.verify(purpose, token)?;
+        Uuid::parse_str(&subject).map_err(|_| MagicLinkError::Invalid)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::configuration::TracingSettings;
+use actix_web::rt::task::JoinHandle;
 use tracing::subscriber::set_global_default;
 use tracing::Subscriber;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
@@ -36,3 +37,12 @@ pub fn init_tracing_subscriber(tracing_subscriber: impl Subscriber + Send + Sync
     LogTracer::init().expect("Failed to set logger");
     set_global_default(tracing_subscriber).expect("Failed to set tracing subscriber");
 }
+
+pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let current_span = tracing::Span::current();
+    actix_web::rt::task::spawn_blocking(move || current_span.in_scope(f))
+}
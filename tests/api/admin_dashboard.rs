@@ -0,0 +1,33 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[actix_rt::test]
+async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app.get_admin_dashboard().await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_rt::test]
+async fn logout_clears_session_state() {
+    // given
+    let app = spawn_app().await;
+    let response = app.login_test_user().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // when
+    let response = app.post_logout().await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("You have successfully logged out."));
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,111 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+
+#[actix_rt::test]
+async fn you_must_be_logged_in_to_see_the_change_password_form() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app.get_change_password().await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_rt::test]
+async fn you_must_be_logged_in_to_change_your_password() {
+    // given
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // when
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": Uuid::new_v4().to_string(),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_rt::test]
+async fn new_password_fields_must_match() {
+    // given
+    let app = spawn_app().await;
+    app.login_test_user().await;
+
+    // when
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": Uuid::new_v4().to_string(),
+            "new_password_check": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page
+        .contains("You entered two different new passwords - the field values must match."));
+}
+
+#[actix_rt::test]
+async fn current_password_must_be_valid() {
+    // given
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+    app.login_test_user().await;
+
+    // when
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": Uuid::new_v4().to_string(),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("The current password is incorrect."));
+}
+
+#[actix_rt::test]
+async fn changing_password_works() {
+    // given
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+    app.login_test_user().await;
+
+    // when
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(html_page.contains("Your password has been changed."));
+
+    app.post_logout().await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -0,0 +1,160 @@
+use crate::helpers::{
+    count_rows, create_confirmed_subscriber, deliver_newsletter, spawn_app, TestApp,
+};
+use uuid::Uuid;
+
+const ARTICLE_URL: &str = "https://example.com/article?id=1&ref=newsletter";
+
+/// An issue linking to the article, and to somewhere that is not tracked.
+fn newsletter_linking_to_the_article() -> serde_json::Value {
+    serde_json::json!({
+        "title": "newsletter title",
+        "content": {
+            "text": "newsletter content in text",
+            "html": "<p>Read <a href=\"https://example.com/article?id=1&amp;ref=newsletter\">the \
+                article</a> or <a href=\"mailto:editor@example.com\">write to us</a>.</p>",
+        }
+    })
+}
+
+/// The tracked link found in the email, pointed at the application under test.
+fn tracked_link(app: &TestApp<'_>, html: &str) -> Option<String> {
+    linkify::LinkFinder::new()
+        .links(html)
+        .map(|link| link.as_str().to_owned())
+        .find(|link| link.contains("/t/c/"))
+        .map(|link| {
+            format!(
+                "{}{}",
+                app.address,
+                reqwest::Url::parse(&link).unwrap().path()
+            )
+        })
+}
+
+fn no_redirects_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn tracked_links_record_the_click_and_redirect_to_their_target() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let html = deliver_newsletter(&app, newsletter_linking_to_the_article()).await;
+    let link = tracked_link(&app, &html).expect("The newsletter has no tracked link.");
+
+    // when
+    let response = no_redirects_client()
+        .get(&link)
+        .header("User-Agent", "Thunderbird/91.0")
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(response.headers().get("Location").unwrap(), ARTICLE_URL);
+    let (email, url, user_agent): (String, String, Option<String>) = sqlx::query_as(
+        "SELECT s.email, c.url, c.user_agent FROM link_clicks c \
+        JOIN newsletter_deliveries d ON d.delivery_id = c.delivery_id \
+        JOIN subscriptions s ON s.id = d.subscriber_id",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(email, "ursula_le_guin@gmail.com");
+    assert_eq!(url, ARTICLE_URL);
+    assert_eq!(user_agent.as_deref(), Some("Thunderbird/91.0"));
+}
+
+#[actix_rt::test]
+async fn only_web_links_are_rewritten() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let html = deliver_newsletter(&app, newsletter_linking_to_the_article()).await;
+
+    // then
+    assert!(!html.contains("https://example.com/article"));
+    assert!(html.contains(r#"href="mailto:editor@example.com""#));
+}
+
+#[actix_rt::test]
+async fn tampered_or_unsigned_links_are_not_redirected() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let html = deliver_newsletter(&app, newsletter_linking_to_the_article()).await;
+    let link = tracked_link(&app, &html).unwrap();
+    let (payload, signature) = link.rsplit_once('/').unwrap().1.split_once('.').unwrap();
+    let forged_payload = base64::encode_config(
+        format!("4102444800:{} https://evil.example.com", Uuid::new_v4()),
+        base64::URL_SAFE_NO_PAD,
+    );
+    let preferences_token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+    let test_cases = [
+        (
+            format!("{}.{}", forged_payload, signature),
+            "a forged target",
+        ),
+        (payload.to_string(), "a missing signature"),
+        (
+            format!("{}.{}x", payload, signature),
+            "a tampered signature",
+        ),
+        (preferences_token, "a token issued for another purpose"),
+        ("https://evil.example.com".to_string(), "a plain URL"),
+    ];
+
+    for (token, description) in test_cases {
+        // when
+        let response = no_redirects_client()
+            .get(format!(
+                "{}/t/c/{}",
+                app.address,
+                url_escape::encode_component(&token)
+            ))
+            .send()
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(
+            response.status().as_u16(),
+            404,
+            "The link with {} was not rejected.",
+            description
+        );
+        assert!(response.headers().get("Location").is_none());
+    }
+    assert_eq!(count_rows(&app, "link_clicks").await, 0);
+}
+
+#[actix_rt::test]
+async fn links_are_left_alone_on_lists_without_tracking() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    app.api_client
+        .patch(format!("{}/lists/newsletter", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({ "tracking_enabled": false }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let html = deliver_newsletter(&app, newsletter_linking_to_the_article()).await;
+
+    // then
+    assert_eq!(tracked_link(&app, &html), None);
+    assert!(html.contains(r#"href="https://example.com/article?id=1&amp;ref=newsletter""#));
+}
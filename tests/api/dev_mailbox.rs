@@ -0,0 +1,57 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+use rust_zero2prod::configuration::{EmailProvider, Environment};
+
+#[actix_rt::test]
+async fn dev_mailbox_lists_emails_captured_in_memory() {
+    // given
+    let app = spawn_app_with(|c| c.email_client.provider = EmailProvider::InMemory).await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let response = reqwest::get(format!("{}/dev/mailbox", &app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Welcome!"));
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+    assert!(html_page.contains("subscription_token="));
+}
+
+#[actix_rt::test]
+async fn dev_mailbox_is_not_available_in_production() {
+    // given
+    let app = spawn_app_with(|c| c.environment = Environment::Production).await;
+
+    // when
+    let response = reqwest::get(format!("{}/dev/mailbox", &app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn dev_mailbox_is_empty_when_nothing_was_sent() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::get(format!("{}/dev/mailbox", &app.address))
+        .await
+        .expect("Failed to execute request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("No emails have been captured yet."));
+}
@@ -7,7 +7,7 @@ async fn health_check_works() {
     let client = reqwest::Client::new();
 
     let response = client
-        .get(&format!("{}/health", &app.address))
+        .get(format!("{}/health", &app.address))
         .send()
         .await
         .expect("Failed to execute request.");
@@ -1,17 +1,29 @@
 use once_cell::sync::Lazy;
-use rust_zero2prod::configuration::{get_configuration, TracingSettings};
+use rust_zero2prod::authentication::compute_password_hash;
+use rust_zero2prod::configuration::{
+    get_configuration, EmailProvider, RetrySettings, Settings, TracingSettings,
+};
+use rust_zero2prod::confirmation_email_worker;
+use rust_zero2prod::email_client::{EmailClient, Mailbox};
+use rust_zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
+use rust_zero2prod::newsletter_scheduler::{try_publish_due_issue, PublishOutcome};
+use rust_zero2prod::startup::{create_email_client, create_template_engine};
+use rust_zero2prod::subscriber_links::SubscriberLinks;
 use rust_zero2prod::telemetry::{get_tracing_subscriber, init_tracing_subscriber};
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::postgres::PgArguments;
 use sqlx::{Arguments, PgPool};
 use std::collections::HashMap;
+use tera::Tera;
 use testcontainers::clients::Cli;
 use testcontainers::core::Port;
 use testcontainers::images::postgres::Postgres;
 use testcontainers::{clients, images, Container, Docker, RunArgs};
-use wiremock::MockServer;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
-static DOCKER: Lazy<Cli> = Lazy::new(|| clients::Cli::default());
+static DOCKER: Lazy<Cli> = Lazy::new(clients::Cli::default);
 
 static TRACING: Lazy<()> = Lazy::new(|| {
     let tracing_settings = TracingSettings {
@@ -53,18 +65,56 @@ pub struct SubscriptionDetails {
     pub status: String,
 }
 
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+        }
+    }
+
+    async fn store(&self, db_pool: &PgPool) {
+        let password_hash = compute_password_hash(Secret::new(self.password.clone()))
+            .expect("Failed to hash the test user password");
+        let mut args = PgArguments::default();
+        args.add(self.user_id);
+        args.add(self.username.as_str());
+        args.add(password_hash.expose_secret().as_str());
+        sqlx::query_with(
+            "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
+            args,
+        )
+        .execute(db_pool)
+        .await
+        .expect("Failed to store the test user");
+    }
+}
+
 pub struct TestApp<'d> {
     pub address: String,
     pub port: u16,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub test_user: TestUser,
+    pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub templates: Tera,
+    pub base_url: String,
+    pub links: SubscriberLinks,
     _db_container: Container<'d, Cli, Postgres>,
 }
 
 impl TestApp<'_> {
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -73,15 +123,182 @@ impl TestApp<'_> {
     }
 
     pub async fn post_newsletters(&self, body: serde_json::Value) -> reqwest::Response {
+        let idempotency_key = Uuid::new_v4().to_string();
+        self.post_newsletters_with_idempotency_key(body, &idempotency_key)
+            .await
+    }
+
+    pub async fn post_newsletters_with_idempotency_key(
+        &self,
+        body: serde_json::Value,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
         reqwest::Client::new()
             .post(format!("{}/newsletters", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
             .header("Content-Type", "application/json")
+            .header("Idempotency-Key", idempotency_key)
             .json(&body)
             .send()
             .await
             .expect("Failed to send the request.")
     }
 
+    /// Drains the delivery queue, waiting for the tasks the background worker might be busy with.
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.templates,
+                &self.links,
+            )
+            .await
+            .unwrap()
+            {
+                let (n_pending,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM issue_delivery_queue WHERE execute_after <= now()",
+                )
+                .fetch_one(&self.db_pool)
+                .await
+                .unwrap();
+                if n_pending == 0 {
+                    break;
+                }
+                actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    /// Drains the confirmation email queue filled by imports.
+    pub async fn dispatch_all_pending_confirmation_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = confirmation_email_worker::try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.templates,
+                &self.base_url,
+            )
+            .await
+            .unwrap()
+            {
+                let (n_pending,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM confirmation_email_queue WHERE execute_after <= now()",
+                )
+                .fetch_one(&self.db_pool)
+                .await
+                .unwrap();
+                if n_pending == 0 {
+                    break;
+                }
+                actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    pub async fn publish_due_issues(&self) {
+        loop {
+            if let PublishOutcome::NothingDue = try_publish_due_issue(&self.db_pool).await.unwrap()
+            {
+                let (n_due,): (i64,) = sqlx::query_as(
+                    "SELECT COUNT(*) FROM newsletter_issues \
+                    WHERE status = 'SCHEDULED' AND published_at <= now()",
+                )
+                .fetch_one(&self.db_pool)
+                .await
+                .unwrap();
+                if n_due == 0 {
+                    break;
+                }
+                actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+        }
+    }
+
+    pub async fn get_scheduled_newsletters(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/newsletters/scheduled", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to send the request.")
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn login_test_user(&self) -> reqwest::Response {
+        self.post_login(&serde_json::json!({
+            "username": &self.test_user.username,
+            "password": &self.test_user.password
+        }))
+        .await
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_admin_dashboard(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/dashboard", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_dashboard_html(&self) -> String {
+        self.get_admin_dashboard().await.text().await.unwrap()
+    }
+
+    pub async fn get_change_password(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/password", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/password", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_saved_subscription(&self, email: &str) -> SubscriptionDetails {
         let mut args = PgArguments::default();
         args.add(email);
@@ -94,6 +311,73 @@ impl TestApp<'_> {
         .expect("Failed to fetch saved subscriptions")
     }
 
+    pub async fn get_unsubscribe_token(&self, email: &str) -> String {
+        let mut args = PgArguments::default();
+        args.add(email);
+        let (unsubscribe_token,): (String,) = sqlx::query_as_with(
+            "SELECT unsubscribe_token FROM subscriptions WHERE email = $1",
+            args,
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .expect("Failed to fetch the unsubscribe token");
+        unsubscribe_token
+    }
+
+    /// A link to the preference center of the subscriber, like the one sent with every issue.
+    pub async fn get_preferences_token(&self, email: &str) -> String {
+        let mut args = PgArguments::default();
+        args.add(email);
+        let (subscriber_id,): (Uuid,) =
+            sqlx::query_as_with("SELECT id FROM subscriptions WHERE email = $1", args)
+                .fetch_one(&self.db_pool)
+                .await
+                .expect("Failed to fetch the subscriber id");
+        self.links.preferences_token(subscriber_id)
+    }
+
+    pub async fn get_preferences(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/subscriptions/preferences", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_preferences_html(&self, token: &str) -> String {
+        self.get_preferences(token).await.text().await.unwrap()
+    }
+
+    pub async fn post_preferences(&self, token: &str, form: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/subscriptions/preferences", &self.address))
+            .query(&[("token", token)])
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Confirms the way a subscriber does, pressing the button on the page the link opens.
+    pub async fn click_confirmation_link(
+        &self,
+        confirmation_link: reqwest::Url,
+    ) -> reqwest::Response {
+        let subscription_token = confirmation_link
+            .query_pairs()
+            .find(|(name, _)| name == "subscription_token")
+            .unwrap()
+            .1
+            .into_owned();
+        self.api_client
+            .post(format!("{}/subscriptions/confirm", &self.address))
+            .form(&[("subscription_token", subscription_token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub fn get_email_body(&self, email_request: &wiremock::Request) -> EmailBody {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -141,6 +425,11 @@ impl TestApp<'_> {
 }
 
 pub async fn spawn_app<'d>() -> Box<TestApp<'d>> {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawns the application with the test configuration adjusted by `customize`.
+pub async fn spawn_app_with<'d>(customize: impl FnOnce(&mut Settings)) -> Box<TestApp<'d>> {
     Lazy::force(&TRACING);
 
     let db_username = "postgres";
@@ -161,7 +450,15 @@ pub async fn spawn_app<'d>() -> Box<TestApp<'d>> {
         c.database.username = Secret::new(db_username.into());
         c.database.password = Secret::new(db_password.into());
         c.application.port = 0;
+        c.email_client.provider = EmailProvider::SendGrid;
         c.email_client.base_url = email_server.uri();
+        c.email_client.retry = RetrySettings {
+            max_attempts: 3,
+            base_delay_millis: 10,
+            max_delay_millis: 100,
+            jitter_millis: 0,
+        };
+        customize(&mut c);
         c
     };
 
@@ -171,17 +468,145 @@ pub async fn spawn_app<'d>() -> Box<TestApp<'d>> {
     let db_pool = rust_zero2prod::startup::create_db_connection_pool(&configuration.database).await;
     let port = app.get_port();
     let address = format!("http://127.0.0.1:{}", port);
-    let _ = tokio::spawn(app.run_until_stopped());
+    tokio::spawn(app.run_until_stopped());
+
+    let test_user = TestUser::generate();
+    test_user.store(&db_pool).await;
+
+    let api_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
 
     Box::new(TestApp {
         address,
         port,
         db_pool,
         email_server,
+        test_user,
+        api_client,
+        email_client: create_email_client(&configuration.email_client, &Mailbox::default()),
+        templates: create_template_engine(&configuration.template_engine),
+        base_url: configuration.application.base_url.clone(),
+        links: SubscriberLinks::from_settings(&configuration),
         _db_container: db_container,
     })
 }
 
+pub async fn create_unconfirmed_subscriber(
+    name: &str,
+    email: &str,
+    app: &TestApp<'_>,
+) -> ConfirmationLinks {
+    subscribe_to_lists(name, email, "", app).await
+}
+
+/// Subscribes to the lists with the given comma separated slugs, the default list when empty.
+pub async fn subscribe_to_lists(
+    name: &str,
+    email: &str,
+    lists: &str,
+    app: &TestApp<'_>,
+) -> ConfirmationLinks {
+    let body = format!(
+        "name={}&email={}&lists={}",
+        url_escape::encode_fragment(name),
+        url_escape::encode_fragment(email),
+        url_escape::encode_fragment(lists)
+    );
+
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+
+    app.get_confirmation_links(email_request)
+}
+
+pub async fn create_confirmed_subscriber(name: &str, email: &str, app: &TestApp<'_>) {
+    let confirmation_links = create_unconfirmed_subscriber(name, email, app).await;
+
+    app.click_confirmation_link(confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+}
+
+pub fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "newsletter title",
+        "content": {
+            "text": "newsletter content in text",
+            "html": "newsletter content in html",
+        }
+    })
+}
+
+/// Publishes an issue to the confirmed subscribers and returns the HTML email the last one got.
+pub async fn deliver_newsletter(app: &TestApp<'_>, body: serde_json::Value) -> String {
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_email_body(&email_request).html
+}
+
+/// The number of rows in `table`, e.g. how many opens or clicks were recorded.
+pub async fn count_rows(app: &TestApp<'_>, table: &str) -> i64 {
+    let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {}", table))
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    count
+}
+
+pub async fn create_list(slug: &str, app: &TestApp<'_>) -> Uuid {
+    let response = app
+        .api_client
+        .post(format!("{}/lists", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({ "slug": slug, "name": slug }))
+        .send()
+        .await
+        .expect("Failed to send the request.");
+    assert_eq!(response.status().as_u16(), 201);
+    let list: serde_json::Value = response.json().await.unwrap();
+    list["list_id"].as_str().unwrap().parse().unwrap()
+}
+
+pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), location);
+}
+
 fn configure_db_container<'d>(
     db_configuration: &DbContainerSettings,
 ) -> (Container<'d, Cli, Postgres>, u16) {
@@ -0,0 +1,138 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn publish_issue(app: &TestApp<'_>, title: &str) {
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": title,
+            "content": {
+                "text": "newsletter content in text",
+                "html": "<p>newsletter content in html</p>",
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_rt::test]
+async fn published_issues_are_listed_in_the_archive() {
+    // given
+    let app = spawn_app().await;
+    publish_issue(&app, "First issue").await;
+    publish_issue(&app, "Second issue").await;
+
+    // when
+    let response = reqwest::get(format!("{}/issues", app.address))
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains(r#"<a href="/issues/first-issue">First issue</a>"#));
+    assert!(html.contains(r#"<a href="/issues/second-issue">Second issue</a>"#));
+    assert!(html.find("Second issue") < html.find("First issue"));
+}
+
+#[actix_rt::test]
+async fn a_published_issue_can_be_read_on_its_own_page() {
+    // given
+    let app = spawn_app().await;
+    publish_issue(&app, "First issue").await;
+
+    // when
+    let response = reqwest::get(format!("{}/issues/first-issue", app.address))
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<p>newsletter content in html</p>"));
+    // The author is an admin login name, half of the publishing credentials.
+    assert!(!html.contains(&app.test_user.username));
+}
+
+#[actix_rt::test]
+async fn issues_with_the_same_title_get_distinct_slugs() {
+    // given
+    let app = spawn_app().await;
+    publish_issue(&app, "Weekly digest").await;
+
+    // when
+    publish_issue(&app, "Weekly digest").await;
+
+    // then
+    let (n_slugs,): (i64,) = sqlx::query_as("SELECT COUNT(DISTINCT slug) FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_slugs, 2);
+}
+
+#[actix_rt::test]
+async fn issues_with_the_same_title_published_concurrently_get_distinct_slugs() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    futures_util::future::join(
+        publish_issue(&app, "Weekly digest"),
+        publish_issue(&app, "Weekly digest"),
+    )
+    .await;
+
+    // then
+    let (n_slugs,): (i64,) = sqlx::query_as("SELECT COUNT(DISTINCT slug) FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_slugs, 2);
+}
+
+#[actix_rt::test]
+async fn unknown_issues_are_not_found() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::get(format!("{}/issues/does-not-exist", app.address))
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn newsletter_emails_link_to_the_issue_in_the_archive() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    publish_issue(&app, "First issue").await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body = app.get_email_body(&email_request);
+    assert!(body.plain.contains(&format!(
+        "View in browser: {}/issues/first-issue",
+        app.base_url
+    )));
+}
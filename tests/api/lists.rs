@@ -0,0 +1,234 @@
+use crate::helpers::{
+    create_confirmed_subscriber, create_list, spawn_app, subscribe_to_lists, TestApp,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn membership_status(app: &TestApp<'_>, email: &str, list: &str) -> String {
+    let (status,): (String,) = sqlx::query_as(
+        "SELECT m.status FROM list_memberships m \
+        JOIN subscriptions s ON s.id = m.subscriber_id \
+        JOIN lists l ON l.list_id = m.list_id \
+        WHERE s.email = $1 AND l.slug = $2",
+    )
+    .bind(email)
+    .bind(list)
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    status
+}
+
+#[actix_rt::test]
+async fn created_lists_are_listed_next_to_the_default_one() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    create_list("weekly", &app).await;
+
+    // then
+    let lists: serde_json::Value = app
+        .api_client
+        .get(format!("{}/lists", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(lists[0]["slug"], "newsletter");
+    assert_eq!(lists[0]["is_default"], true);
+    assert_eq!(lists[1]["slug"], "weekly");
+}
+
+#[actix_rt::test]
+async fn confirmation_is_tracked_per_list() {
+    // given
+    let app = spawn_app().await;
+    create_list("weekly", &app).await;
+
+    // when
+    let confirmation_links =
+        subscribe_to_lists("le guin", "ursula_le_guin@gmail.com", "weekly", &app).await;
+
+    // then
+    assert_eq!(
+        membership_status(&app, "ursula_le_guin@gmail.com", "weekly").await,
+        "PENDING"
+    );
+    app.click_confirmation_link(confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        membership_status(&app, "ursula_le_guin@gmail.com", "weekly").await,
+        "CONFIRMED"
+    );
+    let (n_default_memberships,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM list_memberships m JOIN lists l ON l.list_id = m.list_id \
+        WHERE l.is_default",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_default_memberships, 0);
+}
+
+#[actix_rt::test]
+async fn confirmed_subscribers_joining_another_list_confirm_it_again() {
+    // given
+    let app = spawn_app().await;
+    create_list("weekly", &app).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let confirmation_links =
+        subscribe_to_lists("le guin", "ursula_le_guin@gmail.com", "weekly", &app).await;
+
+    // then
+    assert_eq!(
+        membership_status(&app, "ursula_le_guin@gmail.com", "weekly").await,
+        "PENDING"
+    );
+    assert_eq!(
+        app.get_saved_subscription("ursula_le_guin@gmail.com")
+            .await
+            .status,
+        "CONFIRMED"
+    );
+    app.click_confirmation_link(confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        membership_status(&app, "ursula_le_guin@gmail.com", "weekly").await,
+        "CONFIRMED"
+    );
+    assert_eq!(
+        membership_status(&app, "ursula_le_guin@gmail.com", "newsletter").await,
+        "CONFIRMED"
+    );
+}
+
+#[actix_rt::test]
+async fn newsletters_are_only_delivered_to_the_targeted_lists() {
+    // given
+    let app = spawn_app().await;
+    let weekly_list_id = create_list("weekly", &app).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let confirmation_links =
+        subscribe_to_lists("butler", "octavia_butler@gmail.com", "weekly", &app).await;
+    app.click_confirmation_link(confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Weekly digest",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "<p>newsletter content in html</p>",
+            },
+            "list_ids": [weekly_list_id],
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 202);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(
+        body["personalizations"][0]["to"][0]["email"],
+        "octavia_butler@gmail.com"
+    );
+}
+
+#[actix_rt::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com&lists=nope".into())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn newsletters_to_an_unknown_list_are_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Weekly digest",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "<p>newsletter content in html</p>",
+            },
+            "list_ids": [Uuid::new_v4()],
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn lists_can_be_renamed_and_have_tracking_disabled() {
+    // given
+    let app = spawn_app().await;
+    create_list("weekly", &app).await;
+    let patch = |slug: &str, body: serde_json::Value| {
+        app.api_client
+            .patch(format!("{}/lists/{}", app.address, slug))
+            .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+            .json(&body)
+            .send()
+    };
+
+    // when
+    let response = patch(
+        "weekly",
+        serde_json::json!({ "name": "Weekly digest", "tracking_enabled": false }),
+    )
+    .await
+    .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let list: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(list["name"], "Weekly digest");
+    assert_eq!(list["tracking_enabled"], false);
+    let unknown = patch("monthly", serde_json::json!({ "tracking_enabled": false }))
+        .await
+        .unwrap();
+    assert_eq!(unknown.status().as_u16(), 404);
+    let unnamed = patch("weekly", serde_json::json!({ "name": " " }))
+        .await
+        .unwrap();
+    assert_eq!(unnamed.status().as_u16(), 400);
+}
@@ -0,0 +1,55 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[actix_rt::test]
+async fn an_error_flash_message_is_set_on_failure() {
+    // given
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+
+    // when
+    let response = app.post_login(&login_body).await;
+
+    // then
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p class=\"flash flash-error\"><i>Authentication failed.</i></p>"));
+
+    // the flash message is shown only once
+    let html_page = app.get_login_html().await;
+    assert!(!html_page.contains("Authentication failed."));
+}
+
+#[actix_rt::test]
+async fn redirect_to_admin_dashboard_after_login_success() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app.login_test_user().await;
+
+    // then
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[actix_rt::test]
+async fn login_sets_an_http_only_session_cookie() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app.login_test_user().await;
+
+    // then
+    let cookie = response
+        .cookies()
+        .find(|c| c.name() == "id")
+        .expect("The session cookie was not set.");
+    assert!(cookie.http_only());
+}
@@ -1,5 +1,22 @@
+mod admin_dashboard;
+mod change_password;
+mod click_tracking;
+mod dev_mailbox;
 mod health_check;
 mod helpers;
+mod issues;
+mod lists;
+mod login;
 mod newsletter;
+mod newsletter_preview;
+mod open_tracking;
+mod scheduled_newsletters;
+mod sendgrid_webhook;
+mod subscriber_data;
+mod subscribers;
+mod subscribers_csv;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
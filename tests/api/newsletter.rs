@@ -1,4 +1,6 @@
-use crate::helpers::{spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app};
+use std::time::Duration;
+use uuid::Uuid;
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -24,8 +26,10 @@ async fn newsletters_are_not_delivered_to_unconfirmed_subscribers() {
     });
 
     let response = app.post_newsletters(newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
 
-    assert_eq!(response.status().as_u16(), 200);
+    // then
+    assert_eq!(response.status().as_u16(), 202);
 }
 
 #[actix_rt::test]
@@ -50,13 +54,14 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
     });
 
     let response = app.post_newsletters(newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
 
     // then
     let email_requests = &app.email_server.received_requests().await.unwrap();
     let last_email_request = email_requests.last().unwrap();
     let email_body = &app.get_email_body(last_email_request);
 
-    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.status().as_u16(), 202);
     assert!(email_body
         .html
         .as_str()
@@ -98,47 +103,226 @@ async fn newsletters_return_400_for_invalid_data() {
     }
 }
 
-async fn create_unconfirmed_subscriber(
-    name: &str,
-    email: &str,
-    app: &TestApp<'_>,
-) -> ConfirmationLinks {
-    let body = format!(
-        "name={}&email={}",
-        url_escape::encode_fragment(name),
-        url_escape::encode_fragment(email)
+#[actix_rt::test]
+async fn requests_missing_authorization_are_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.address))
+        .json(&serde_json::json!({
+            "title": "newsletter title",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "newsletter content in html",
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to send the request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
     );
+}
+
+#[actix_rt::test]
+async fn non_existing_user_is_rejected() {
+    // given
+    let app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+    let password = Uuid::new_v4().to_string();
 
-    let _mock_guard = Mock::given(path("/v3/mail/send"))
+    // when
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.address))
+        .basic_auth(username, Some(password))
+        .json(&serde_json::json!({
+            "title": "newsletter title",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "newsletter content in html",
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to send the request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[actix_rt::test]
+async fn invalid_password_is_rejected() {
+    // given
+    let app = spawn_app().await;
+    let username = &app.test_user.username;
+    let password = Uuid::new_v4().to_string();
+    assert_ne!(app.test_user.password, password);
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.address))
+        .basic_auth(username, Some(password))
+        .json(&serde_json::json!({
+            "title": "newsletter title",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "newsletter content in html",
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to send the request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[actix_rt::test]
+async fn requests_without_idempotency_key_are_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({
+            "title": "newsletter title",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "newsletter content in html",
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to send the request.");
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn newsletter_creation_is_idempotent() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
         .expect(1)
-        .mount_as_scoped(&app.email_server)
+        .mount(&app.email_server)
         .await;
 
+    let newsletter_request_body = serde_json::json!({
+        "title": "newsletter title",
+        "content": {
+            "text": "newsletter content in text",
+            "html": "newsletter content in html",
+        }
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+
     // when
-    app.post_subscriptions(body)
-        .await
-        .error_for_status()
-        .unwrap();
+    let first_response = app
+        .post_newsletters_with_idempotency_key(newsletter_request_body.clone(), &idempotency_key)
+        .await;
+    let second_response = app
+        .post_newsletters_with_idempotency_key(newsletter_request_body, &idempotency_key)
+        .await;
+    app.dispatch_all_pending_emails().await;
 
-    let email_request = &app
-        .email_server
-        .received_requests()
-        .await
-        .unwrap()
-        .pop()
-        .unwrap();
+    // then
+    assert_eq!(first_response.status().as_u16(), 202);
+    assert_eq!(second_response.status().as_u16(), 202);
+    assert_eq!(
+        first_response.text().await.unwrap(),
+        second_response.text().await.unwrap()
+    );
+}
+
+#[actix_rt::test]
+async fn concurrent_newsletter_submission_is_handled_gracefully() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "newsletter title",
+        "content": {
+            "text": "newsletter content in text",
+            "html": "newsletter content in html",
+        }
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    // when
+    let first_request = app
+        .post_newsletters_with_idempotency_key(newsletter_request_body.clone(), &idempotency_key);
+    let second_request =
+        app.post_newsletters_with_idempotency_key(newsletter_request_body, &idempotency_key);
+    let (first_response, second_response) = tokio::join!(first_request, second_request);
+    app.dispatch_all_pending_emails().await;
 
-    app.get_confirmation_links(email_request)
+    // then
+    assert_eq!(first_response.status(), second_response.status());
+    assert_eq!(
+        first_response.text().await.unwrap(),
+        second_response.text().await.unwrap()
+    );
 }
 
-async fn create_confirmed_subscriber(name: &str, email: &str, app: &TestApp<'_>) {
-    let confirmation_links = create_unconfirmed_subscriber(name, email, app).await;
+#[actix_rt::test]
+async fn failed_deliveries_are_kept_in_the_queue_for_a_later_retry() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "newsletter title",
+        "content": {
+            "text": "newsletter content in text",
+            "html": "newsletter content in html",
+        }
+    });
+
+    // when
+    let response = app.post_newsletters(newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
 
-    reqwest::get(confirmation_links.html)
+    // then
+    assert_eq!(response.status().as_u16(), 202);
+    let (n_retries,): (i16,) = sqlx::query_as("SELECT n_retries FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
         .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
+        .expect("Failed to fetch the queued delivery task");
+    assert_eq!(n_retries, 1);
 }
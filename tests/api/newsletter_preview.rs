@@ -0,0 +1,137 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+fn newsletter_content() -> serde_json::Value {
+    serde_json::json!({
+        "text": "newsletter content in text",
+        "html": "<p>newsletter content in html</p>",
+    })
+}
+
+#[actix_rt::test]
+async fn preview_returns_the_rendered_issue_without_sending_it() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!("{}/newsletters/preview", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({
+            "title": "First issue",
+            "content": newsletter_content(),
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["subject"], "First issue");
+    let text = body["text"].as_str().unwrap();
+    assert!(text.contains("newsletter content in text"));
+    assert!(text.contains(&format!("{}/issues/first-issue", app.base_url)));
+    assert!(body["html"].as_str().unwrap().contains("Unsubscribe"));
+}
+
+#[actix_rt::test]
+async fn test_send_only_emails_the_given_recipients() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!("{}/newsletters/test", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({
+            "title": "First issue",
+            "content": newsletter_content(),
+            "recipients": ["editor@example.com", "reviewer@example.com"],
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let recipients: Vec<String> = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/v3/mail/send")
+        .skip(1)
+        .map(|request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["personalizations"][0]["to"][0]["email"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(recipients, ["editor@example.com", "reviewer@example.com"]);
+}
+
+#[actix_rt::test]
+async fn test_send_rejects_invalid_recipients() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!("{}/newsletters/test", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({
+            "title": "First issue",
+            "content": newsletter_content(),
+            "recipients": ["not-an-email"],
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn preview_requires_authentication() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!("{}/newsletters/preview", app.address))
+        .json(&serde_json::json!({
+            "title": "First issue",
+            "content": newsletter_content(),
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,132 @@
+use crate::helpers::{
+    count_rows, create_confirmed_subscriber, deliver_newsletter, newsletter_request_body, spawn_app,
+};
+use uuid::Uuid;
+
+fn tracking_pixel(html: &str) -> Option<String> {
+    linkify::LinkFinder::new()
+        .links(html)
+        .map(|link| link.as_str().to_owned())
+        .find(|link| link.contains("/t/o/"))
+}
+
+#[actix_rt::test]
+async fn loading_the_tracking_pixel_records_an_open() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let html = deliver_newsletter(&app, newsletter_request_body()).await;
+    let pixel = tracking_pixel(&html).expect("The newsletter has no tracking pixel.");
+    let pixel = format!(
+        "{}{}",
+        app.address,
+        reqwest::Url::parse(&pixel).unwrap().path()
+    );
+
+    // when
+    let response = app
+        .api_client
+        .get(&pixel)
+        .header("User-Agent", "Thunderbird/91.0")
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "image/gif");
+    assert!(response
+        .headers()
+        .get("Cache-Control")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("no-store"));
+    assert_eq!(&response.bytes().await.unwrap()[..6], b"GIF89a");
+    let (email, user_agent): (String, Option<String>) = sqlx::query_as(
+        "SELECT s.email, o.user_agent FROM email_opens o \
+        JOIN newsletter_deliveries d ON d.delivery_id = o.delivery_id \
+        JOIN subscriptions s ON s.id = d.subscriber_id",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(email, "ursula_le_guin@gmail.com");
+    assert_eq!(user_agent.as_deref(), Some("Thunderbird/91.0"));
+}
+
+#[actix_rt::test]
+async fn every_delivery_gets_its_own_tracking_pixel() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let first = tracking_pixel(&deliver_newsletter(&app, newsletter_request_body()).await).unwrap();
+    app.email_server.reset().await;
+    let second =
+        tracking_pixel(&deliver_newsletter(&app, newsletter_request_body()).await).unwrap();
+
+    // then
+    assert_ne!(first, second);
+    let (n_deliveries,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_deliveries, 2);
+}
+
+#[actix_rt::test]
+async fn issues_sent_to_lists_without_tracking_carry_no_pixel() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    app.api_client
+        .patch(format!("{}/lists/newsletter", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({ "tracking_enabled": false }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let html = deliver_newsletter(&app, newsletter_request_body()).await;
+
+    // then
+    assert_eq!(tracking_pixel(&html), None);
+    let (delivery_id, tracked): (Uuid, bool) =
+        sqlx::query_as("SELECT delivery_id, tracked FROM newsletter_deliveries")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert!(!tracked);
+    app.api_client
+        .get(format!("{}/t/o/{}.gif", app.address, delivery_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(count_rows(&app, "email_opens").await, 0);
+}
+
+#[actix_rt::test]
+async fn unknown_deliveries_get_the_pixel_without_recording_anything() {
+    // given
+    let app = spawn_app().await;
+
+    for delivery_id in [Uuid::new_v4().to_string(), "not-a-delivery".to_string()] {
+        // when
+        let response = app
+            .api_client
+            .get(format!("{}/t/o/{}.gif", app.address, delivery_id))
+            .send()
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), "image/gif");
+    }
+    assert_eq!(count_rows(&app, "email_opens").await, 0);
+}
@@ -0,0 +1,212 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn schedule_issue(app: &TestApp<'_>, title: &str) -> Uuid {
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": title,
+            "content": {
+                "text": "newsletter content in text",
+                "html": "<p>newsletter content in html</p>",
+            },
+            "send_at": Utc::now() + Duration::days(3),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "SCHEDULED");
+    body["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+async fn make_due(app: &TestApp<'_>, newsletter_issue_id: Uuid) {
+    sqlx::query(
+        "UPDATE newsletter_issues SET published_at = now() - interval '1 minute' \
+        WHERE newsletter_issue_id = $1",
+    )
+    .bind(newsletter_issue_id)
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_are_not_sent_before_their_send_time() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    schedule_issue(&app, "Monday issue").await;
+    app.publish_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let archive = reqwest::get(format!("{}/issues", app.address))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!archive.contains("Monday issue"));
+    let scheduled: serde_json::Value = app.get_scheduled_newsletters().await.json().await.unwrap();
+    assert_eq!(scheduled.as_array().unwrap().len(), 1);
+    assert_eq!(scheduled[0]["title"], "Monday issue");
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_are_sent_once_due() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let newsletter_issue_id = schedule_issue(&app, "Monday issue").await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    make_due(&app, newsletter_issue_id).await;
+    app.publish_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let response = reqwest::get(format!("{}/issues/monday-issue", app.address))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let scheduled: serde_json::Value = app.get_scheduled_newsletters().await.json().await.unwrap();
+    assert!(scheduled.as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_can_be_rescheduled() {
+    // given
+    let app = spawn_app().await;
+    let newsletter_issue_id = schedule_issue(&app, "Monday issue").await;
+    let send_at = (Utc::now() + Duration::days(7)).date().and_hms(9, 0, 0);
+
+    // when
+    let response = app
+        .api_client
+        .patch(format!(
+            "{}/newsletters/scheduled/{}",
+            app.address, newsletter_issue_id
+        ))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({ "send_at": send_at }))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let scheduled: serde_json::Value = app.get_scheduled_newsletters().await.json().await.unwrap();
+    assert_eq!(scheduled[0]["send_at"], serde_json::json!(send_at));
+}
+
+#[actix_rt::test]
+async fn cancelled_issues_are_never_sent() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let newsletter_issue_id = schedule_issue(&app, "Monday issue").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .api_client
+        .delete(format!(
+            "{}/newsletters/scheduled/{}",
+            app.address, newsletter_issue_id
+        ))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 204);
+    make_due(&app, newsletter_issue_id).await;
+    app.publish_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+    let scheduled: serde_json::Value = app.get_scheduled_newsletters().await.json().await.unwrap();
+    assert!(scheduled.as_array().unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn issues_cannot_be_scheduled_in_the_past() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Yesterday's issue",
+            "content": {
+                "text": "newsletter content in text",
+                "html": "<p>newsletter content in html</p>",
+            },
+            "send_at": Utc::now() - Duration::days(1),
+        }))
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_rt::test]
+async fn unknown_scheduled_issues_are_not_found() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = app
+        .api_client
+        .delete(format!(
+            "{}/newsletters/scheduled/{}",
+            app.address,
+            Uuid::new_v4()
+        ))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn scheduled_issues_are_only_listed_to_authenticated_users() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::get(format!("{}/newsletters/scheduled", app.address))
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,400 @@
+use crate::helpers::{
+    create_confirmed_subscriber, create_unconfirmed_subscriber, newsletter_request_body, spawn_app,
+    spawn_app_with, TestApp,
+};
+use chrono::Utc;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::pkcs8::EncodePublicKey;
+use rust_zero2prod::configuration::SendGridWebhookSettings;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+/// The app expects events signed with `signing_key`, as SendGrid would sign them.
+async fn spawn_app_verifying<'d>(signing_key: &SigningKey) -> Box<TestApp<'d>> {
+    let der = signing_key.verifying_key().to_public_key_der().unwrap();
+    let verification_key = base64::encode(der.as_bytes());
+    spawn_app_with(|c| {
+        c.email_client.sendgrid_webhook = Some(SendGridWebhookSettings { verification_key })
+    })
+    .await
+}
+
+fn event(event_id: &str, event: &str, email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "sg_event_id": event_id,
+        "event": event,
+        "email": email,
+        "timestamp": 1656665990,
+        "reason": "550 5.1.1 The email account that you tried to reach does not exist.",
+    })
+}
+
+async fn post_events(
+    app: &TestApp<'_>,
+    signing_key: &SigningKey,
+    events: serde_json::Value,
+) -> reqwest::Response {
+    let timestamp = Utc::now().timestamp().to_string();
+    post_events_signed_at(app, signing_key, events, &timestamp).await
+}
+
+async fn post_events_signed_at(
+    app: &TestApp<'_>,
+    signing_key: &SigningKey,
+    events: serde_json::Value,
+    timestamp: &str,
+) -> reqwest::Response {
+    let body = events.to_string();
+    let signature: Signature = signing_key.sign(format!("{}{}", timestamp, body).as_bytes());
+    app.api_client
+        .post(format!("{}/webhooks/sendgrid", &app.address))
+        .header("Content-Type", "application/json")
+        .header("X-Twilio-Email-Event-Webhook-Timestamp", timestamp)
+        .header(
+            "X-Twilio-Email-Event-Webhook-Signature",
+            base64::encode(signature.to_der().as_bytes()),
+        )
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn recorded_events(app: &TestApp<'_>) -> Vec<String> {
+    sqlx::query_as::<_, (String,)>("SELECT event_type FROM email_events ORDER BY event_type")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(event_type,)| event_type)
+        .collect()
+}
+
+#[actix_rt::test]
+async fn hard_bounce_suppresses_the_subscriber_and_stops_newsletters() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let mut bounce = event("bounce-1", "bounce", "Ursula_Le_Guin@gmail.com");
+    bounce["type"] = "bounce".into();
+
+    // when
+    let response = post_events(&app, &signing_key, serde_json::json!([bounce])).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "SUPPRESSED");
+    assert_eq!(recorded_events(&app).await, vec!["bounce"]);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_rt::test]
+async fn spam_report_suppresses_the_subscriber() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let events = serde_json::json!([event(
+        "spamreport-1",
+        "spamreport",
+        "ursula_le_guin@gmail.com"
+    )]);
+
+    // when
+    let response = post_events(&app, &signing_key, events).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "SUPPRESSED");
+}
+
+#[actix_rt::test]
+async fn soft_bounces_and_drops_are_recorded_without_suppressing() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let mut blocked = event("bounce-1", "bounce", "ursula_le_guin@gmail.com");
+    blocked["type"] = "blocked".into();
+    let dropped = event("dropped-1", "dropped", "ursula_le_guin@gmail.com");
+    let delivered = event("delivered-1", "delivered", "ursula_le_guin@gmail.com");
+
+    // when
+    let response = post_events(
+        &app,
+        &signing_key,
+        serde_json::json!([blocked, dropped, delivered]),
+    )
+    .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+    assert_eq!(recorded_events(&app).await, vec!["bounce", "dropped"]);
+}
+
+#[actix_rt::test]
+async fn unsubscribe_event_unsubscribes_the_subscriber() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let events = serde_json::json!([event(
+        "unsubscribe-1",
+        "unsubscribe",
+        "ursula_le_guin@gmail.com"
+    )]);
+
+    // when
+    let response = post_events(&app, &signing_key, events).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "UNSUBSCRIBED");
+}
+
+#[actix_rt::test]
+async fn replayed_events_are_processed_once() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let events = serde_json::json!([event(
+        "spamreport-1",
+        "spamreport",
+        "ursula_le_guin@gmail.com"
+    )]);
+    post_events(&app, &signing_key, events.clone()).await;
+    // Resubscribed since, the old report must not suppress them again.
+    sqlx::query("UPDATE subscriptions SET status = 'CONFIRMED'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = post_events(&app, &signing_key, events).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+    assert_eq!(recorded_events(&app).await, vec!["spamreport"]);
+}
+
+#[actix_rt::test]
+async fn suppressed_subscribers_signing_up_again_stay_suppressed() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let events = serde_json::json!([event(
+        "spamreport-1",
+        "spamreport",
+        "ursula_le_guin@gmail.com"
+    )]);
+    post_events(&app, &signing_key, events).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "SUPPRESSED");
+}
+
+#[actix_rt::test]
+async fn unsubscribe_links_do_not_lift_the_suppression() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let unsubscribe_token = app.get_unsubscribe_token("ursula_le_guin@gmail.com").await;
+    let events = serde_json::json!([event(
+        "spamreport-1",
+        "spamreport",
+        "ursula_le_guin@gmail.com"
+    )]);
+    post_events(&app, &signing_key, events).await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address, unsubscribe_token
+        ))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "SUPPRESSED");
+}
+
+#[actix_rt::test]
+async fn confirmation_links_sent_before_a_bounce_do_not_lift_the_suppression() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    let confirmation_links =
+        create_unconfirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let mut bounce = event("bounce-1", "bounce", "ursula_le_guin@gmail.com");
+    bounce["type"] = "bounce".into();
+    post_events(&app, &signing_key, serde_json::json!([bounce])).await;
+
+    // when
+    let response = app.click_confirmation_link(confirmation_links.html).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!response.text().await.unwrap().contains("confirmed!"));
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "SUPPRESSED");
+    let (n_confirmed,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM list_memberships WHERE status = 'CONFIRMED'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(n_confirmed, 0);
+}
+
+#[actix_rt::test]
+async fn events_about_unknown_addresses_are_ignored() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    let events = serde_json::json!([event("spamreport-1", "spamreport", "nobody@example.com")]);
+
+    // when
+    let response = post_events(&app, &signing_key, events).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(recorded_events(&app).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn events_signed_with_another_key_are_rejected() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let other_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let events = serde_json::json!([event(
+        "spamreport-1",
+        "spamreport",
+        "ursula_le_guin@gmail.com"
+    )]);
+
+    // when
+    let response = post_events(&app, &other_key, events).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+    assert!(recorded_events(&app).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn replayed_requests_signed_long_ago_are_rejected() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let events = serde_json::json!([event(
+        "spamreport-1",
+        "spamreport",
+        "ursula_le_guin@gmail.com"
+    )]);
+    let an_hour_ago = (Utc::now().timestamp() - 3600).to_string();
+
+    // when
+    let response = post_events_signed_at(&app, &signing_key, events, &an_hour_ago).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+    assert!(recorded_events(&app).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn events_with_an_out_of_range_timestamp_are_rejected() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let mut spam_report = event("spamreport-1", "spamreport", "ursula_le_guin@gmail.com");
+    spam_report["timestamp"] = i64::MAX.into();
+
+    // when
+    let response = post_events(&app, &signing_key, serde_json::json!([spam_report])).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+    assert!(recorded_events(&app).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn unsigned_events_are_rejected() {
+    // given
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+    let app = spawn_app_verifying(&signing_key).await;
+
+    // when
+    let response = app
+        .api_client
+        .post(format!("{}/webhooks/sendgrid", &app.address))
+        .json(&serde_json::json!([event(
+            "spamreport-1",
+            "spamreport",
+            "ursula_le_guin@gmail.com"
+        )]))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn events_are_rejected_when_the_webhook_is_not_configured() {
+    // given
+    let app = spawn_app().await;
+    let signing_key = SigningKey::random(&mut rand::rngs::OsRng);
+
+    // when
+    let response = post_events(&app, &signing_key, serde_json::json!([])).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,107 @@
+use crate::helpers::{count_rows, create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn post_data_subject_request(
+    app: &TestApp<'_>,
+    action: &str,
+    email: &str,
+) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/subscribers/{}", app.address, action))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&serde_json::json!({ "email": email }))
+        .send()
+        .await
+        .expect("Failed to send the request.")
+}
+
+#[actix_rt::test]
+async fn export_returns_everything_stored_about_the_subscriber() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    create_confirmed_subscriber("butler", "octavia_butler@gmail.com", &app).await;
+
+    // when
+    let response = post_data_subject_request(&app, "export", "ursula_le_guin@gmail.com").await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscription"]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(export["subscription"]["name"], "le guin");
+    assert_eq!(export["subscription"]["status"], "CONFIRMED");
+    assert_eq!(export["list_memberships"][0]["list_slug"], "newsletter");
+    assert_eq!(export["list_memberships"][0]["status"], "CONFIRMED");
+    assert_eq!(export["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert!(!export["subscription_tokens"][0]["consumed_at"].is_null());
+    assert_eq!(export["pending_deliveries"], serde_json::json!([]));
+}
+
+#[actix_rt::test]
+async fn addresses_are_found_whatever_their_case() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let export = post_data_subject_request(&app, "export", "Ursula_Le_Guin@Gmail.com").await;
+    let erasure = post_data_subject_request(&app, "erase", "URSULA_LE_GUIN@GMAIL.COM").await;
+
+    // then
+    assert_eq!(export.status().as_u16(), 200);
+    assert_eq!(erasure.status().as_u16(), 204);
+    assert_eq!(count_rows(&app, "subscriptions").await, 0);
+}
+
+#[actix_rt::test]
+async fn export_and_erasure_of_unknown_addresses_return_404() {
+    // given
+    let app = spawn_app().await;
+
+    for action in ["export", "erase"] {
+        // when
+        let response = post_data_subject_request(&app, action, "nobody@example.com").await;
+
+        // then
+        assert_eq!(response.status().as_u16(), 404, "Action: {}", action);
+    }
+}
+
+#[actix_rt::test]
+async fn data_subject_requests_require_authentication() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    for action in ["export", "erase"] {
+        // when
+        let response = reqwest::Client::new()
+            .post(format!("{}/admin/subscribers/{}", app.address, action))
+            .json(&serde_json::json!({ "email": "ursula_le_guin@gmail.com" }))
+            .send()
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status().as_u16(), 401, "Action: {}", action);
+    }
+    assert_eq!(count_rows(&app, "subscriptions").await, 1);
+}
+
+#[actix_rt::test]
+async fn erasure_deletes_the_subscriber_with_their_tokens_and_memberships() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let response = post_data_subject_request(&app, "erase", "ursula_le_guin@gmail.com").await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(count_rows(&app, "subscriptions").await, 0);
+    assert_eq!(count_rows(&app, "subscription_tokens").await, 0);
+    assert_eq!(count_rows(&app, "list_memberships").await, 0);
+    let response = post_data_subject_request(&app, "export", "ursula_le_guin@gmail.com").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
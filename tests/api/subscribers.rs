@@ -0,0 +1,296 @@
+use crate::helpers::{
+    create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app, TestApp,
+};
+use uuid::Uuid;
+
+async fn get_subscribers(app: &TestApp<'_>, query: &[(&str, &str)]) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/subscribers", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .query(query)
+        .send()
+        .await
+        .expect("Failed to send the request.")
+}
+
+async fn get_subscriber(app: &TestApp<'_>, id: Uuid) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/subscribers/{}", app.address, id))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await
+        .expect("Failed to send the request.")
+}
+
+async fn patch_subscriber(
+    app: &TestApp<'_>,
+    id: Uuid,
+    body: serde_json::Value,
+) -> reqwest::Response {
+    app.api_client
+        .patch(format!("{}/admin/subscribers/{}", app.address, id))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to send the request.")
+}
+
+async fn subscriber_id(app: &TestApp<'_>, email: &str) -> Uuid {
+    let (id,): (Uuid,) = sqlx::query_as("SELECT id FROM subscriptions WHERE email = $1")
+        .bind(email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    id
+}
+
+fn emails(page: &serde_json::Value) -> Vec<String> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|subscriber| subscriber["email"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[actix_rt::test]
+async fn subscribers_can_be_filtered_by_status_and_email() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    create_confirmed_subscriber("butler", "octavia_butler@gmail.com", &app).await;
+    create_unconfirmed_subscriber("jemisin", "nk_jemisin@gmail.com", &app).await;
+
+    // when
+    let confirmed = get_subscribers(&app, &[("status", "CONFIRMED")]).await;
+    let matching = get_subscribers(&app, &[("email", "LE_GUIN")]).await;
+
+    // then
+    assert_eq!(confirmed.status().as_u16(), 200);
+    let mut confirmed = emails(&confirmed.json().await.unwrap());
+    confirmed.sort();
+    assert_eq!(
+        confirmed,
+        vec!["octavia_butler@gmail.com", "ursula_le_guin@gmail.com"]
+    );
+    let matching: serde_json::Value = matching.json().await.unwrap();
+    assert_eq!(emails(&matching), vec!["ursula_le_guin@gmail.com"]);
+    assert_eq!(matching["subscribers"][0]["name"], "le guin");
+    assert_eq!(matching["subscribers"][0]["frequency"], "EVERY_ISSUE");
+    assert!(matching["next_cursor"].is_null());
+}
+
+#[actix_rt::test]
+async fn email_filter_treats_wildcards_literally() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let response = get_subscribers(&app, &[("email", "%")]).await;
+
+    // then
+    let page: serde_json::Value = response.json().await.unwrap();
+    assert!(emails(&page).is_empty());
+}
+
+#[actix_rt::test]
+async fn pages_follow_each_other_through_the_cursor() {
+    // given
+    let app = spawn_app().await;
+    let mut expected = Vec::new();
+    for name in ["a", "b", "c", "d", "e"] {
+        let email = format!("{}@example.com", name);
+        create_unconfirmed_subscriber(name, &email, &app).await;
+        expected.push(email);
+    }
+
+    // when
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = vec![("sort", "email"), ("order", "asc"), ("limit", "2")];
+        if let Some(cursor) = &cursor {
+            query.push(("after", cursor));
+        }
+        let page: serde_json::Value = get_subscribers(&app, &query).await.json().await.unwrap();
+        seen.extend(emails(&page));
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    // then
+    assert_eq!(seen, expected);
+}
+
+#[actix_rt::test]
+async fn invalid_filters_are_rejected_with_400() {
+    // given
+    let app = spawn_app().await;
+    let test_cases = [
+        (vec![("status", "ACTIVE")], "an unknown status"),
+        (vec![("after", "not-a-cursor")], "a malformed cursor"),
+        (vec![("sort", "name")], "an unknown sort field"),
+    ];
+
+    for (query, description) in test_cases {
+        // when
+        let response = get_subscribers(&app, &query).await;
+
+        // then
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject {}.",
+            description
+        );
+    }
+}
+
+#[actix_rt::test]
+async fn a_subscriber_is_returned_with_their_list_memberships() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let id = subscriber_id(&app, "ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = get_subscriber(&app, id).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["id"], id.to_string());
+    assert_eq!(subscriber["status"], "CONFIRMED");
+    assert_eq!(subscriber["lists"][0]["list_slug"], "newsletter");
+    assert_eq!(subscriber["lists"][0]["status"], "CONFIRMED");
+    assert_eq!(
+        get_subscriber(&app, Uuid::new_v4()).await.status().as_u16(),
+        404
+    );
+}
+
+#[actix_rt::test]
+async fn patch_updates_the_subscriber_and_their_memberships() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let id = subscriber_id(&app, "ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = patch_subscriber(
+        &app,
+        id,
+        serde_json::json!({
+            "name": "Ursula K. Le Guin",
+            "email": "ursula@example.com",
+            "status": "UNSUBSCRIBED",
+            "frequency": "WEEKLY"
+        }),
+    )
+    .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["name"], "Ursula K. Le Guin");
+    assert_eq!(subscriber["email"], "ursula@example.com");
+    assert_eq!(subscriber["status"], "UNSUBSCRIBED");
+    assert_eq!(subscriber["frequency"], "WEEKLY");
+    assert_eq!(subscriber["lists"][0]["status"], "UNSUBSCRIBED");
+}
+
+#[actix_rt::test]
+async fn patch_rejects_invalid_values_with_400() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    create_confirmed_subscriber("butler", "octavia_butler@gmail.com", &app).await;
+    let id = subscriber_id(&app, "ursula_le_guin@gmail.com").await;
+    let test_cases = [
+        (
+            serde_json::json!({ "email": "definitely-not-an-email" }),
+            "an invalid email",
+        ),
+        (serde_json::json!({ "name": "" }), "an empty name"),
+        (
+            serde_json::json!({ "status": "ACTIVE" }),
+            "an unknown status",
+        ),
+        (
+            serde_json::json!({ "frequency": "DAILY" }),
+            "an unknown frequency",
+        ),
+        (
+            serde_json::json!({ "email": "octavia_butler@gmail.com" }),
+            "an email used by another subscriber",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        // when
+        let response = patch_subscriber(&app, id, body).await;
+
+        // then
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject {}.",
+            description
+        );
+    }
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.name, "le guin");
+}
+
+#[actix_rt::test]
+async fn delete_erases_the_subscriber() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let id = subscriber_id(&app, "ursula_le_guin@gmail.com").await;
+    let delete = || {
+        app.api_client
+            .delete(format!("{}/admin/subscribers/{}", app.address, id))
+            .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+            .send()
+    };
+
+    // when
+    let response = delete().await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 204);
+    assert_eq!(get_subscriber(&app, id).await.status().as_u16(), 404);
+    assert_eq!(delete().await.unwrap().status().as_u16(), 404);
+}
+
+#[actix_rt::test]
+async fn subscriber_api_requires_authentication() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let id = subscriber_id(&app, "ursula_le_guin@gmail.com").await;
+    let client = reqwest::Client::new();
+    let requests = [
+        client.get(format!("{}/admin/subscribers", app.address)),
+        client.get(format!("{}/admin/subscribers/{}", app.address, id)),
+        client
+            .patch(format!("{}/admin/subscribers/{}", app.address, id))
+            .json(&serde_json::json!({ "name": "someone else" })),
+        client.delete(format!("{}/admin/subscribers/{}", app.address, id)),
+    ];
+
+    for request in requests {
+        // when
+        let response = request.send().await.unwrap();
+
+        // then
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.name, "le guin");
+}
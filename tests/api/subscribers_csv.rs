@@ -0,0 +1,322 @@
+use crate::helpers::{create_confirmed_subscriber, create_list, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn post_import(app: &TestApp<'_>, query: &str, csv: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!(
+            "{}/admin/subscribers/import?{}",
+            app.address, query
+        ))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .header("Content-Type", "text/csv")
+        .body(csv.to_string())
+        .send()
+        .await
+        .expect("Failed to send the request.")
+}
+
+async fn get_export(app: &TestApp<'_>) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/admin/subscribers/export.csv", app.address))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .send()
+        .await
+        .expect("Failed to send the request.")
+}
+
+async fn saved_statuses(app: &TestApp<'_>) -> Vec<(String, String)> {
+    sqlx::query_as("SELECT email, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn confirmed_import_stores_valid_rows_and_reports_the_others() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("butler", "octavia_butler@gmail.com", &app).await;
+    let csv = "email,name,status,consent_date\n\
+               ursula_le_guin@gmail.com,le guin,,2021-03-01\n\
+               definitely-not-an-email,someone,,\n\
+               nk_jemisin@gmail.com,,,\n\
+               octavia_butler@gmail.com,butler,,\n\
+               ted_chiang@gmail.com,chiang,UNSUBSCRIBED,\n\
+               ursula_le_guin@gmail.com,le guin again,,\n\
+               iain_banks@gmail.com,banks,ACTIVE,\n";
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = post_import(&app, "mode=confirmed", csv).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["confirmation_emails_queued"], 0);
+    let failed_lines: Vec<_> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(failed_lines, vec![3, 4, 5, 7, 8]);
+    assert_eq!(report["errors"][0]["email"], "definitely-not-an-email");
+    assert_eq!(
+        saved_statuses(&app).await,
+        vec![
+            ("octavia_butler@gmail.com".into(), "CONFIRMED".into()),
+            ("ted_chiang@gmail.com".into(), "UNSUBSCRIBED".into()),
+            ("ursula_le_guin@gmail.com".into(), "CONFIRMED".into()),
+        ]
+    );
+    let (subscribed_at,): (chrono::DateTime<chrono::Utc>,) =
+        sqlx::query_as("SELECT subscribed_at FROM subscriptions WHERE email = $1")
+            .bind("ursula_le_guin@gmail.com")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(subscribed_at.to_rfc3339(), "2021-03-01T00:00:00+00:00");
+}
+
+#[actix_rt::test]
+async fn double_opt_in_import_queues_a_confirmation_email_for_each_new_subscriber() {
+    // given
+    let app = spawn_app().await;
+    let csv = "email,name,status\n\
+               ursula_le_guin@gmail.com,le guin,CONFIRMED\n\
+               ted_chiang@gmail.com,chiang,UNSUBSCRIBED\n";
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = post_import(&app, "mode=double_opt_in", csv).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["confirmation_emails_queued"], 1);
+    assert_eq!(
+        app.get_saved_subscription("ursula_le_guin@gmail.com")
+            .await
+            .status,
+        "PENDING"
+    );
+
+    app.dispatch_all_pending_confirmation_emails().await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    app.click_confirmation_link(confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        app.get_saved_subscription("ursula_le_guin@gmail.com")
+            .await
+            .status,
+        "CONFIRMED"
+    );
+}
+
+#[actix_rt::test]
+async fn import_does_not_wait_for_the_email_provider() {
+    // given
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = post_import(
+        &app,
+        "mode=double_opt_in",
+        "email,name\nursula_le_guin@gmail.com,le guin\n",
+    )
+    .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_confirmation_emails().await;
+    let (n_retries,): (i16,) = sqlx::query_as("SELECT n_retries FROM confirmation_email_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_retries, 1);
+}
+
+#[actix_rt::test]
+async fn queued_confirmation_emails_skip_subscribers_confirmed_in_the_meantime() {
+    // given
+    let app = spawn_app().await;
+    post_import(
+        &app,
+        "mode=double_opt_in",
+        "email,name\nursula_le_guin@gmail.com,le guin\n",
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    sqlx::query("UPDATE subscriptions SET status = 'CONFIRMED'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    Mock::given(path("/v3/mail/send"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.dispatch_all_pending_confirmation_emails().await;
+
+    // then
+    let (n_queued,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM confirmation_email_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_queued, 0);
+}
+
+#[actix_rt::test]
+async fn imported_subscribers_join_the_requested_lists() {
+    // given
+    let app = spawn_app().await;
+    create_list("releases", &app).await;
+
+    // when
+    let response = post_import(
+        &app,
+        "mode=confirmed&lists=releases",
+        "email,name\nursula_le_guin@gmail.com,le guin\n",
+    )
+    .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let lists: Vec<(String, String)> = sqlx::query_as(
+        "SELECT l.slug, m.status FROM list_memberships m JOIN lists l ON l.list_id = m.list_id",
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(lists, vec![("releases".into(), "CONFIRMED".into())]);
+}
+
+#[actix_rt::test]
+async fn imports_without_required_columns_or_with_unknown_lists_are_rejected_with_400() {
+    // given
+    let app = spawn_app().await;
+    let test_cases = [
+        (
+            "mode=confirmed",
+            "name\nle guin\n",
+            "a missing email column",
+        ),
+        (
+            "mode=confirmed",
+            "email\nursula_le_guin@gmail.com\n",
+            "a missing name column",
+        ),
+        (
+            "mode=confirmed&lists=unknown",
+            "email,name\nursula_le_guin@gmail.com,le guin\n",
+            "an unknown list",
+        ),
+        ("mode=maybe", "email,name\n", "an unknown mode"),
+    ];
+
+    for (query, csv, description) in test_cases {
+        // when
+        let response = post_import(&app, query, csv).await;
+
+        // then
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject {}.",
+            description
+        );
+    }
+    assert!(saved_statuses(&app).await.is_empty());
+}
+
+#[actix_rt::test]
+async fn export_returns_every_subscriber_as_csv() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    create_confirmed_subscriber("Butler, Octavia", "octavia_butler@gmail.com", &app).await;
+
+    // when
+    let response = get_export(&app).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text().await.unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "email,name,status,frequency,subscribed_at");
+    assert!(lines[1].starts_with("ursula_le_guin@gmail.com,le guin,CONFIRMED,EVERY_ISSUE,"));
+    assert!(lines[2]
+        .starts_with(r#"octavia_butler@gmail.com,"Butler, Octavia",CONFIRMED,EVERY_ISSUE,"#));
+}
+
+#[actix_rt::test]
+async fn an_export_can_be_imported_back() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let csv = get_export(&app).await.text().await.unwrap();
+    sqlx::query("DELETE FROM subscriptions")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let response = post_import(&app, "mode=confirmed", &csv).await;
+
+    // then
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 1);
+    assert_eq!(report["errors"], serde_json::json!([]));
+    assert_eq!(get_export(&app).await.text().await.unwrap(), csv);
+}
+
+#[actix_rt::test]
+async fn import_and_export_require_authentication() {
+    // given
+    let app = spawn_app().await;
+    let client = reqwest::Client::new();
+    let requests = [
+        client
+            .post(format!("{}/admin/subscribers/import", app.address))
+            .body("email,name\nursula_le_guin@gmail.com,le guin\n"),
+        client.get(format!("{}/admin/subscribers/export.csv", app.address)),
+    ];
+
+    for request in requests {
+        // when
+        let response = request.send().await.unwrap();
+
+        // then
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    assert!(saved_statuses(&app).await.is_empty());
+}
@@ -1,4 +1,6 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{count_rows, create_confirmed_subscriber, spawn_app, spawn_app_with};
+use rust_zero2prod::configuration::RateLimitStoreKind;
+use rust_zero2prod::rate_limit::delete_full_buckets;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -121,7 +123,7 @@ async fn subscribe_fails_when_sending_confirmation_email_fails() {
     Mock::given(path("/v3/mail/send"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(500))
-        .expect(1)
+        .expect(3)
         .mount(&app.email_server)
         .await;
 
@@ -170,3 +172,272 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // then
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[actix_rt::test]
+async fn subscribing_twice_while_pending_resends_a_new_confirmation_link() {
+    // given
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    // when
+    let response = app.post_subscriptions(body.into()).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_link = app.get_confirmation_links(&email_requests[0]).html;
+    let second_link = app.get_confirmation_links(&email_requests[1]).html;
+    assert_ne!(first_link, second_link);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "PENDING");
+}
+
+#[actix_rt::test]
+async fn concurrent_sign_ups_for_the_same_address_both_succeed() {
+    // given
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let (first, second) = futures_util::future::join(
+        app.post_subscriptions(body.into()),
+        app.post_subscriptions(body.into()),
+    )
+    .await;
+
+    // then
+    assert_eq!(first.status().as_u16(), 200);
+    assert_eq!(second.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "PENDING");
+}
+
+#[actix_rt::test]
+async fn subscribing_a_confirmed_subscriber_again_succeeds_without_an_email() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn an_unsubscribed_address_can_opt_in_again() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let unsubscribe_token = app.get_unsubscribe_token("ursula_le_guin@gmail.com").await;
+    reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address, unsubscribe_token
+        ))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app
+        .post_subscriptions("name=ursula&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "PENDING");
+    assert_eq!(saved.name, "ursula");
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_link = app.get_confirmation_links(&email_request).html;
+    app.click_confirmation_link(confirmation_link)
+        .await
+        .error_for_status()
+        .unwrap();
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn subscriptions_over_the_client_ip_limit_are_rejected_with_a_429() {
+    // given
+    let app = spawn_app_with(|settings| {
+        settings.rate_limit.subscriptions_per_client_ip.capacity = 2;
+        settings
+            .rate_limit
+            .subscriptions_per_client_ip
+            .period_seconds = 3600;
+    })
+    .await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for email in ["ursula_le_guin%40gmail.com", "octavia_butler%40gmail.com"] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={}", email))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // when
+    let response = app
+        .post_subscriptions("name=le%20guin&email=iain_banks%40gmail.com".into())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(retry_after, 1800);
+}
+
+#[actix_rt::test]
+async fn confirmation_emails_over_the_address_limit_are_rejected_with_a_429() {
+    // given
+    let app = spawn_app_with(|settings| {
+        settings.rate_limit.store = RateLimitStoreKind::InMemory;
+        settings.rate_limit.confirmation_emails_per_address.capacity = 1;
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    // when
+    let response = app.post_subscriptions(body.into()).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[actix_rt::test]
+async fn confirmed_and_new_addresses_run_into_the_address_limit_alike() {
+    // given
+    let app = spawn_app_with(|settings| {
+        settings.rate_limit.store = RateLimitStoreKind::InMemory;
+        settings.rate_limit.confirmation_emails_per_address.capacity = 2;
+    })
+    .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // Imported, so the address has not used any of its limit yet.
+    app.api_client
+        .post(format!(
+            "{}/admin/subscribers/import?mode=confirmed",
+            app.address
+        ))
+        .basic_auth(&app.test_user.username, Some(&app.test_user.password))
+        .header("Content-Type", "text/csv")
+        .body("email,name\nursula_le_guin@gmail.com,le guin\n")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let mut answers = Vec::new();
+    for email in ["ursula_le_guin%40gmail.com", "octavia_butler%40gmail.com"] {
+        let mut statuses = Vec::new();
+        for _ in 0..4 {
+            let response = app
+                .post_subscriptions(format!("name=someone&email={}", email))
+                .await;
+            statuses.push(response.status().as_u16());
+        }
+        answers.push(statuses);
+    }
+
+    // then
+    assert_eq!(answers[0], vec![200, 200, 429, 429]);
+    assert_eq!(answers[0], answers[1]);
+}
+
+#[actix_rt::test]
+async fn full_rate_limit_buckets_are_forgotten() {
+    // given
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    sqlx::query("UPDATE rate_limit_buckets SET full_at = now() WHERE key LIKE 'email:%'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // when
+    let n_deleted = delete_full_buckets(&app.db_pool).await.unwrap();
+
+    // then
+    assert_eq!(n_deleted, 1);
+    assert_eq!(count_rows(&app, "rate_limit_buckets").await, 1);
+}
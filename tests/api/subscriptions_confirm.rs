@@ -1,4 +1,6 @@
-use crate::helpers::spawn_app;
+use crate::helpers::{
+    assert_is_redirect_to, create_unconfirmed_subscriber, spawn_app, spawn_app_with,
+};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -59,9 +61,8 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     let confirmation_links = &app.get_confirmation_links(email_request);
 
     // when
-    reqwest::get(confirmation_links.html.clone())
+    app.click_confirmation_link(confirmation_links.html.clone())
         .await
-        .unwrap()
         .error_for_status()
         .unwrap();
 
@@ -71,3 +72,248 @@ async fn clicking_on_the_confirmation_link_confirms_a_subscriber() {
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.status, "CONFIRMED");
 }
+
+#[actix_rt::test]
+async fn opening_the_confirmation_link_only_shows_a_confirm_button() {
+    // given
+    let app = spawn_app().await;
+    let confirmation_links =
+        create_unconfirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("confirmForm"));
+    assert!(html_page.contains("Confirm my subscription"));
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "PENDING");
+}
+
+#[actix_rt::test]
+async fn opening_the_confirmation_link_confirms_right_away_with_one_click_confirmation() {
+    // given
+    let app = spawn_app_with(|settings| settings.subscriptions.one_click_confirmation = true).await;
+    let confirmation_links =
+        create_unconfirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    // when
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn a_confirmation_link_can_only_be_used_once() {
+    // given
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = &app.get_confirmation_links(email_request);
+    app.click_confirmation_link(confirmation_links.html.clone())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // when
+    let response = app
+        .click_confirmation_link(confirmation_links.html.clone())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("This link has been used already"));
+}
+
+#[actix_rt::test]
+async fn confirming_a_subscription_renders_a_branded_landing_page() {
+    // given
+    let app = spawn_app_with(|settings| settings.branding.name = "Earthsea Gazette".into()).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = &app.get_confirmation_links(email_request);
+
+    // when
+    let response = app
+        .click_confirmation_link(confirmation_links.html.clone())
+        .await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Earthsea Gazette"));
+    assert!(html_page.contains("your subscription is confirmed"));
+}
+
+#[actix_rt::test]
+async fn unknown_confirmation_tokens_render_the_invalid_link_page() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let responses = [
+        reqwest::get(format!(
+            "{}/subscriptions/confirm?subscription_token=aaaaaaaaaaaaaaaaaaaaaaaaa",
+            app.address
+        ))
+        .await
+        .unwrap(),
+        reqwest::get(format!(
+            "{}/subscriptions/confirm?subscription_token=not-a-token",
+            app.address
+        ))
+        .await
+        .unwrap(),
+    ];
+
+    // then
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 401);
+        assert!(response
+            .text()
+            .await
+            .unwrap()
+            .contains("This confirmation link is not valid."));
+    }
+}
+
+#[actix_rt::test]
+async fn confirmation_redirects_to_the_configured_url_with_the_outcome() {
+    // given
+    let app = spawn_app_with(|settings| {
+        settings.subscriptions.confirmation_redirect_url =
+            Some("https://example.com/welcome?source=email".into())
+    })
+    .await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = &app.get_confirmation_links(email_request);
+
+    // when
+    let first_response = app
+        .click_confirmation_link(confirmation_links.html.clone())
+        .await;
+    let second_response = app
+        .click_confirmation_link(confirmation_links.html.clone())
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &first_response,
+        "https://example.com/welcome?source=email&status=confirmed",
+    );
+    assert_is_redirect_to(
+        &second_response,
+        "https://example.com/welcome?source=email&status=already_confirmed",
+    );
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn expired_confirmation_links_are_rejected_with_a_resend_form() {
+    // given
+    let app =
+        spawn_app_with(|settings| settings.subscriptions.confirmation_token_ttl_minutes = 0).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = &app.get_confirmation_links(email_request);
+
+    // when
+    let response = reqwest::get(confirmation_links.html.clone()).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("/subscriptions/confirm/resend"));
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "PENDING");
+}
+
+#[actix_rt::test]
+async fn resending_a_confirmation_sends_a_new_link() {
+    // given
+    let app =
+        spawn_app_with(|settings| settings.subscriptions.confirmation_token_ttl_minutes = 0).await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_subscriptions(body.into()).await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let expired_link = app.get_confirmation_links(email_request).html;
+    let expired_token = expired_link
+        .query_pairs()
+        .find(|(name, _)| name == "subscription_token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscriptions/confirm/resend", app.address))
+        .form(&[("subscription_token", expired_token)])
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = &app.email_server.received_requests().await.unwrap()[1];
+    let new_link = app.get_confirmation_links(email_request).html;
+    assert_ne!(new_link, expired_link);
+}
@@ -0,0 +1,149 @@
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn post_data_request(app: &TestApp<'_>, email: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/subscriptions/data", app.address))
+        .form(&[("email", email)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+/// Requests the data links for `email` and returns the (export, erasure) links from the email.
+async fn request_data_links(app: &TestApp<'_>, email: &str) -> (reqwest::Url, reqwest::Url) {
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    post_data_request(app, email)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let text = app.get_email_body(&email_request).plain;
+    let find_link = |path: &str| {
+        let link = linkify::LinkFinder::new()
+            .links(&text)
+            .map(|link| link.as_str().to_owned())
+            .find(|link| link.contains(path))
+            .unwrap();
+        let mut link = reqwest::Url::parse(&link).unwrap();
+        link.set_port(Some(app.port)).unwrap();
+        link
+    };
+    (
+        find_link("/subscriptions/data/export"),
+        find_link("/subscriptions/data/erase"),
+    )
+}
+
+#[actix_rt::test]
+async fn data_request_for_an_unknown_address_sends_no_email() {
+    // given
+    let app = spawn_app().await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = post_data_request(&app, "nobody@example.com").await;
+
+    // then - the answer does not reveal whether the address is subscribed
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("If we hold any data about nobody@example.com"));
+}
+
+#[actix_rt::test]
+async fn export_link_downloads_the_subscriber_data_as_json() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let (export_link, _) = request_data_links(&app, "ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = reqwest::get(export_link).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("Content-Disposition")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscription"]["email"], "ursula_le_guin@gmail.com");
+}
+
+#[actix_rt::test]
+async fn tampered_export_link_is_rejected_with_a_401() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let (_, erasure_link) = request_data_links(&app, "ursula_le_guin@gmail.com").await;
+    // A token signed for the erasure does not grant the export.
+    let mut forged_link = erasure_link.clone();
+    forged_link.set_path("/subscriptions/data/export");
+
+    // when
+    let response = reqwest::get(forged_link).await.unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn erasure_link_erases_the_subscriber_only_once_confirmed() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let (_, erasure_link) = request_data_links(&app, "ursula_le_guin@gmail.com").await;
+
+    // when
+    let form_response = reqwest::get(erasure_link.clone()).await.unwrap();
+    let (n_subscribers_after_opening,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM subscriptions")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    let erase_response = reqwest::Client::new()
+        .post(erasure_link)
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(form_response.status().as_u16(), 200);
+    assert!(form_response
+        .text()
+        .await
+        .unwrap()
+        .contains("dataErasureForm"));
+    assert_eq!(n_subscribers_after_opening, 1);
+    assert_eq!(erase_response.status().as_u16(), 200);
+    let (n_subscribers,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 0);
+}
@@ -0,0 +1,437 @@
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_list, newsletter_request_body,
+    spawn_app, spawn_app_with,
+};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[actix_rt::test]
+async fn newsletters_carry_a_link_to_the_preference_center() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let text = app.get_email_body(&email_request).plain;
+    let preferences_link = linkify::LinkFinder::new()
+        .links(&text)
+        .map(|link| link.as_str().to_owned())
+        .find(|link| link.contains("/subscriptions/preferences?token="))
+        .expect("The newsletter has no link to the preference center.");
+    let token = reqwest::Url::parse(&preferences_link)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned();
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("preferencesForm"));
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+}
+
+#[actix_rt::test]
+async fn tampered_preferences_link_is_rejected_with_a_401() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+    let forged_token = format!("{}x", token);
+
+    // when
+    let get_response = app.get_preferences(&forged_token).await;
+    let post_response = app
+        .post_preferences(
+            &forged_token,
+            &[
+                ("name", "mallory"),
+                ("email", "ursula_le_guin@gmail.com"),
+                ("frequency", "EVERY_ISSUE"),
+            ],
+        )
+        .await;
+
+    // then
+    assert_eq!(get_response.status().as_u16(), 401);
+    assert_eq!(post_response.status().as_u16(), 401);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.name, "le guin");
+}
+
+#[actix_rt::test]
+async fn expired_preferences_link_is_rejected_with_a_410() {
+    // given
+    let app =
+        spawn_app_with(|settings| settings.subscriptions.preferences_link_ttl_minutes = 0).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = app.get_preferences(&token).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(response.text().await.unwrap().contains("expired"));
+}
+
+#[actix_rt::test]
+async fn subscriber_can_update_their_name_frequency_and_lists() {
+    // given
+    let app = spawn_app().await;
+    let rust_list_id = create_list("rust", &app).await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+    let rust_list_id = rust_list_id.to_string();
+
+    // when
+    let response = app
+        .post_preferences(
+            &token,
+            &[
+                ("name", "Ursula K. Le Guin"),
+                ("email", "ursula_le_guin@gmail.com"),
+                ("frequency", "WEEKLY"),
+                ("lists", &rust_list_id),
+            ],
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={}", token),
+    );
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Your preferences have been saved."));
+
+    let (name, frequency): (String, String) = sqlx::query_as(
+        "SELECT name, frequency FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(name, "Ursula K. Le Guin");
+    assert_eq!(frequency, "WEEKLY");
+
+    let memberships: Vec<(String, String)> = sqlx::query_as(
+        "SELECT l.slug, m.status FROM list_memberships m \
+        JOIN lists l ON l.list_id = m.list_id ORDER BY l.slug",
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        memberships,
+        vec![
+            ("newsletter".to_string(), "UNSUBSCRIBED".to_string()),
+            ("rust".to_string(), "CONFIRMED".to_string()),
+        ]
+    );
+}
+
+#[actix_rt::test]
+async fn invalid_preferences_are_rejected_with_a_flash_message() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = app
+        .post_preferences(
+            &token,
+            &[
+                ("name", ""),
+                ("email", "ursula_le_guin@gmail.com"),
+                ("frequency", "DAILY"),
+            ],
+        )
+        .await;
+
+    // then
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={}", token),
+    );
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("flash-error"));
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.name, "le guin");
+}
+
+#[actix_rt::test]
+async fn new_email_address_is_only_used_once_it_is_confirmed() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+    let default_list_id: (Uuid,) = sqlx::query_as("SELECT list_id FROM lists WHERE is_default")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let default_list_id = default_list_id.0.to_string();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_preferences(
+        &token,
+        &[
+            ("name", "le guin"),
+            ("email", "ursula@example.com"),
+            ("frequency", "EVERY_ISSUE"),
+            ("lists", &default_list_id),
+        ],
+    )
+    .await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(
+        body["personalizations"][0]["to"][0]["email"],
+        "ursula@example.com"
+    );
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    app.click_confirmation_link(confirmation_links.html)
+        .await
+        .error_for_status()
+        .unwrap();
+    let saved = app.get_saved_subscription("ursula@example.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn email_address_of_another_subscriber_cannot_be_taken_over() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    create_confirmed_subscriber("butler", "octavia_butler@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_preferences(
+        &token,
+        &[
+            ("name", "le guin"),
+            ("email", "octavia_butler@gmail.com"),
+            ("frequency", "EVERY_ISSUE"),
+        ],
+    )
+    .await;
+
+    // then
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Another subscription uses this email address already."));
+}
+
+#[actix_rt::test]
+async fn confirming_an_address_taken_in_the_meantime_shows_a_page_explaining_it() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let token = app.get_preferences_token("ursula_le_guin@gmail.com").await;
+    let confirmation_links = {
+        let _mock_guard = Mock::given(path("/v3/mail/send"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.post_preferences(
+            &token,
+            &[
+                ("name", "le guin"),
+                ("email", "ursula@example.com"),
+                ("frequency", "EVERY_ISSUE"),
+            ],
+        )
+        .await;
+        let email_request = app
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        app.get_confirmation_links(&email_request)
+    };
+    create_confirmed_subscriber("someone else", "ursula@example.com", &app).await;
+
+    // when
+    let response = app.click_confirmation_link(confirmation_links.html).await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 409);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Another subscription uses this email address already"));
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn weekly_subscribers_get_at_most_one_issue_per_week() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    sqlx::query("UPDATE subscriptions SET frequency = 'WEEKLY'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    for _ in 0..2 {
+        app.post_newsletters(newsletter_request_body())
+            .await
+            .error_for_status()
+            .unwrap();
+        app.dispatch_all_pending_emails().await;
+    }
+
+    // then - the mock verifies that a single email went out
+}
+
+#[actix_rt::test]
+async fn weekly_subscribers_get_the_issues_published_since_in_one_digest() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    sqlx::query("UPDATE subscriptions SET frequency = 'WEEKLY'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    for title in ["first issue", "second issue", "third issue"] {
+        let mut body = newsletter_request_body();
+        body["title"] = title.into();
+        app.post_newsletters(body).await.error_for_status().unwrap();
+        app.dispatch_all_pending_emails().await;
+    }
+
+    // when - a week goes by
+    sqlx::query("UPDATE subscriptions SET last_delivered_at = now() - interval '8 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE issue_delivery_queue SET execute_after = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let request_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(request_body["subject"], "Your digest: 2 new issues");
+    let email_body = app.get_email_body(&email_request);
+    let second = email_body.plain.find("second issue").unwrap();
+    let third = email_body.plain.find("third issue").unwrap();
+    assert!(second < third);
+    assert!(!email_body.html.contains("first issue"));
+    let (n_deliveries,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM newsletter_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_deliveries, 3);
+}
+
+#[actix_rt::test]
+async fn issues_queued_before_a_digest_went_out_wait_for_the_next_one() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    sqlx::query("UPDATE subscriptions SET frequency = 'WEEKLY'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_newsletters(newsletter_request_body())
+        .await
+        .error_for_status()
+        .unwrap();
+    // Another worker delivered a digest in the meantime.
+    sqlx::query("UPDATE subscriptions SET last_delivered_at = now() - interval '1 day'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    Mock::given(path("/v3/mail/send"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let (postponed,): (bool,) = sqlx::query_as(
+        "SELECT execute_after > now() + interval '5 days' FROM issue_delivery_queue",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(postponed);
+}
@@ -0,0 +1,162 @@
+use crate::helpers::{create_confirmed_subscriber, newsletter_request_body, spawn_app};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[actix_rt::test]
+async fn newsletters_carry_an_unsubscribe_link_and_list_unsubscribe_headers() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let unsubscribe_token = app.get_unsubscribe_token("ursula_le_guin@gmail.com").await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let unsubscribe_link = format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        app.base_url, unsubscribe_token
+    );
+    assert_eq!(
+        body["headers"]["List-Unsubscribe"],
+        format!("<{}>", unsubscribe_link)
+    );
+    assert_eq!(
+        body["headers"]["List-Unsubscribe-Post"],
+        "List-Unsubscribe=One-Click"
+    );
+    assert!(app
+        .get_email_body(&email_request)
+        .plain
+        .contains(&unsubscribe_link));
+}
+
+#[actix_rt::test]
+async fn unsubscribe_link_shows_a_confirmation_form() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let unsubscribe_token = app.get_unsubscribe_token("ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        app.address, unsubscribe_token
+    ))
+    .await
+    .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains("unsubscribeForm"));
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "CONFIRMED");
+}
+
+#[actix_rt::test]
+async fn one_click_unsubscribe_marks_the_subscriber_as_unsubscribed() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let unsubscribe_token = app.get_unsubscribe_token("ursula_le_guin@gmail.com").await;
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address, unsubscribe_token
+        ))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = app.get_saved_subscription("ursula_le_guin@gmail.com").await;
+    assert_eq!(saved.status, "UNSUBSCRIBED");
+}
+
+#[actix_rt::test]
+async fn newsletters_are_not_delivered_to_unsubscribed_subscribers() {
+    // given
+    let app = spawn_app().await;
+    create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com", &app).await;
+    let unsubscribe_token = app.get_unsubscribe_token("ursula_le_guin@gmail.com").await;
+    reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address, unsubscribe_token
+        ))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(path("/v3/mail/send"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // when
+    let response = app.post_newsletters(newsletter_request_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    // then
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_rt::test]
+async fn unsubscribing_with_an_unknown_token_is_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/subscriptions/unsubscribe?token={}",
+            app.address, "aaaaaaaaaaaaaaaaaaaaaaaaa"
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_rt::test]
+async fn unsubscribing_with_a_malformed_token_is_rejected() {
+    // given
+    let app = spawn_app().await;
+
+    // when
+    let response = reqwest::get(format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        app.address, "not-a-token"
+    ))
+    .await
+    .unwrap();
+
+    // then
+    assert_eq!(response.status().as_u16(), 400);
+}